@@ -8,36 +8,230 @@
 	non_ascii_idents,
 	nonstandard_style,
 	noop_method_call,
-	pointer_structural_match,
-	private_in_public,
 	rust_2018_idioms,
 	unused_qualifications
 )]
 #![warn(clippy::pedantic)]
 #![forbid(unsafe_code)]
 
-use std::collections::HashMap;
+use std::collections::BTreeMap;
+use std::ops::Bound;
+use std::path::PathBuf;
 
-fn main() {
-	let raw =
-		std::fs::read_to_string("../dict.json").expect("reading dictionary from `../dict.json`");
-	let dict: HashMap<Box<str>, Box<str>> =
-		serde_json::from_str(&raw).expect("deserializing dictionary JSON");
+use anyhow::{ensure, Context as _};
+use argh::FromArgs;
+use regex::{Regex, RegexBuilder};
+use sordahe::chords::ChordTable;
+use sordahe::dict::{SourceDict, SourceEntry, Strokes};
+
+/// Look up outlines and translations in stenotype dictionaries.
+#[allow(clippy::struct_excessive_bools /* Command-line switches. */)]
+#[derive(FromArgs, Debug)]
+struct Args {
+	/// path to a dictionary JSON; may be repeated (default: ../dict.json)
+	#[argh(option, short = 'D')]
+	dict: Vec<PathBuf>,
+	/// look up translations by outline, rather than outlines by translation
+	#[argh(switch, short = 'f')]
+	forward: bool,
+	/// find everything that starts with the search
+	#[argh(switch, short = 'p')]
+	prefix: bool,
+	/// treat the search as a regular expression
+	#[argh(switch, short = 'r')]
+	regex: bool,
+	/// ignore case when matching
+	#[argh(switch, short = 'i')]
+	ignore_case: bool,
+	/// match translations by the text they write, ignoring formatting, so that `{^ing}` is found by "ing"
+	#[argh(switch, short = 't')]
+	text_only: bool,
+	/// with -f, read searches as pseudo-steno, like "NAN" for TPHAPB
+	#[argh(switch, short = 's')]
+	pseudo: bool,
+	/// path to a chord table JSON for pseudo-steno dictionaries and searches (default: built-in)
+	#[argh(option, short = 'c')]
+	chords: Option<PathBuf>,
+	/// searches to run; if none are given, they are read from stdin
+	#[argh(positional)]
+	searches: Vec<String>,
+}
+
+#[derive(Debug)]
+struct Hit<'a> {
+	dict: usize,
+	strokes: &'a Strokes,
+	entry: &'a SourceEntry,
+}
+
+/// Hits grouped by the text that searches are matched against.
+type Index<'a> = BTreeMap<String, Vec<Hit<'a>>>;
+
+#[derive(Debug)]
+enum Matcher {
+	Exact(String),
+	Prefix(String),
+	Regex(Regex),
+}
+
+#[allow(clippy::struct_excessive_bools /* Copied from the command-line switches. */)]
+#[derive(Debug)]
+struct Searcher<'a> {
+	index: Index<'a>,
+	forward: bool,
+	/// Set if searches are in pseudo-steno.
+	chords: Option<&'a ChordTable>,
+	lowercase: bool,
+	prefix: bool,
+	regex: bool,
+	ignore_case: bool,
+}
+
+impl<'a> Searcher<'a> {
+	fn new(dicts: &'a [SourceDict], chords: Option<&'a ChordTable>, args: &Args) -> Self {
+		// Regexes handle case-insensitivity themselves.
+		let lowercase = args.ignore_case && !args.regex;
+
+		let mut index = Index::new();
+		for (i, dict) in dicts.iter().enumerate() {
+			for (strokes, entry) in dict.iter() {
+				let mut key = if args.forward {
+					strokes.to_string()
+				} else if args.text_only {
+					entry.entry.plain_text()
+				} else {
+					entry.raw.to_string()
+				};
+				if lowercase {
+					key = key.to_lowercase();
+				}
+				index.entry(key).or_default().push(Hit {
+					dict: i,
+					strokes,
+					entry,
+				});
+			}
+		}
+
+		Self {
+			index,
+			forward: args.forward,
+			chords,
+			lowercase,
+			prefix: args.prefix,
+			regex: args.regex,
+			ignore_case: args.ignore_case,
+		}
+	}
+
+	fn matcher(&self, search: &str) -> anyhow::Result<Matcher> {
+		if self.regex {
+			let regex = RegexBuilder::new(search)
+				.case_insensitive(self.ignore_case)
+				.build()
+				.context("parsing regex")?;
+			return Ok(Matcher::Regex(regex));
+		}
+
+		let mut search = if let (true, Some(chords)) = (self.forward, self.chords) {
+			chords.parse_pseudo_strokes(search)?.to_string()
+		} else if self.forward {
+			// Normalize the outline if possible, so that, e.g., "S-S" finds "SS".
+			search
+				.parse::<Strokes>()
+				.map_or_else(|_| search.to_owned(), |strokes| strokes.to_string())
+		} else {
+			search.to_owned()
+		};
+		if self.lowercase {
+			search = search.to_lowercase();
+		}
+
+		Ok(if self.prefix {
+			Matcher::Prefix(search)
+		} else {
+			Matcher::Exact(search)
+		})
+	}
+
+	fn search(&self, matcher: &Matcher) -> Vec<&Hit<'a>> {
+		let mut hits: Vec<_> = match matcher {
+			Matcher::Exact(search) => self.index.get(search).into_iter().flatten().collect(),
+			Matcher::Prefix(search) => self
+				.index
+				.range::<str, _>((Bound::Included(search.as_str()), Bound::Unbounded))
+				.take_while(|(key, _)| key.starts_with(search.as_str()))
+				.flat_map(|(_, hits)| hits)
+				.collect(),
+			Matcher::Regex(regex) => self
+				.index
+				.iter()
+				.filter(|(key, _)| regex.is_match(key))
+				.flat_map(|(_, hits)| hits)
+				.collect(),
+		};
+		hits.sort_by_cached_key(|hit| {
+			let strokes = hit.strokes.to_string();
+			(hit.dict, hit.strokes.num_strokes(), strokes.len(), strokes)
+		});
+		hits
+	}
+}
+
+fn main() -> anyhow::Result<()> {
+	let mut args: Args = argh::from_env();
+	ensure!(
+		!(args.prefix && args.regex),
+		"--prefix and --regex are mutually exclusive"
+	);
+	ensure!(
+		!(args.pseudo && (args.regex || args.prefix)),
+		"--pseudo can't be combined with --prefix or --regex"
+	);
+	ensure!(args.forward || !args.pseudo, "--pseudo requires --forward");
+	if args.dict.is_empty() {
+		args.dict.push("../dict.json".into());
+	}
+
+	let chords = ChordTable::load_or_builtin(args.chords.as_deref())?;
+	let dicts = args
+		.dict
+		.iter()
+		.map(|path| {
+			SourceDict::load(path, &chords)
+				.with_context(|| format!("loading dictionary from {}", path.display()))
+		})
+		.collect::<anyhow::Result<Vec<_>>>()?;
+	let searcher = Searcher::new(&dicts, args.pseudo.then_some(&chords), &args);
+
+	let run = |search: &str| -> anyhow::Result<()> {
+		let matcher = searcher.matcher(search)?;
+		for hit in searcher.search(&matcher) {
+			println!(
+				"{} {:?} ({})",
+				hit.strokes,
+				hit.entry.raw,
+				args.dict[hit.dict].display(),
+			);
+		}
+		Ok(())
+	};
+
+	if !args.searches.is_empty() {
+		for search in &args.searches {
+			run(search)?;
+		}
+		return Ok(());
+	}
 
 	eprint!("> ");
 	for search in std::io::stdin().lines() {
-		let search = search.expect("reading from stdin");
-		let mut entries = dict
-			.iter()
-			.filter(|(_stroke, text)| ***text == search)
-			.map(|(stroke, _text)| stroke)
-			.collect::<Vec<_>>();
-		entries.sort_by_key(|entry| (entry.chars().filter(|&ch| ch == '/').count(), entry.len()));
-
-		for entry in entries {
-			println!("{entry}");
+		let search = search.context("reading from stdin")?;
+		if let Err(error) = run(&search) {
+			eprintln!("{error:#}");
 		}
-
 		eprint!("> ");
 	}
+
+	Ok(())
 }
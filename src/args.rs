@@ -4,41 +4,189 @@ use std::path::PathBuf;
 use std::str::FromStr;
 
 use argh::FromArgs;
+use serde_with::DeserializeFromStr;
 use thiserror::Error;
 
 /// Stenotype for Wayland.
 #[derive(FromArgs, Debug)]
 pub struct Args {
-	/// path to the dictionary JSON
-	#[argh(option, short = 'D', default = r#""dict.json".into()"#)]
-	pub dict: PathBuf,
+	/// path to a dictionary JSON; may be repeated, with earlier dictionaries taking priority (default: dict.json)
+	#[argh(option, short = 'D')]
+	pub dict: Vec<PathBuf>,
 	/// path to the word list
 	#[argh(option, short = 'W', default = r#""words.txt".into()"#)]
 	pub word_list: PathBuf,
+	/// path to a chord table JSON, mapping chord names to keys, for pseudo-steno dictionaries and outlines (default: built-in)
+	#[argh(option, short = 'C')]
+	pub chords: Option<PathBuf>,
 	#[argh(subcommand)]
-	pub frontend: Frontend,
+	pub command: Command,
 }
 
 #[derive(FromArgs, Debug)]
 #[argh(subcommand)]
-pub enum Frontend {
+pub enum Command {
 	InputMethod(InputMethodArgs),
 	VirtualKeyboard(VirtualKeyboardArgs),
+	Diff(DiffArgs),
+	Merge(MergeArgs),
+	Suggest(SuggestArgs),
+	Explain(ExplainArgs),
+	EmulateMachine(EmulateMachineArgs),
+	Replay(ReplayArgs),
 }
 
 /// Run as an input method, translating from the normal keyboard to stenotype.
+#[allow(clippy::struct_excessive_bools /* They're independent switches. */)]
 #[derive(FromArgs, Debug)]
 #[argh(subcommand, name = "input-method")]
-pub struct InputMethodArgs {}
+pub struct InputMethodArgs {
+	/// path to a config JSON with layouts and other settings for the input method
+	#[argh(option, short = 'c')]
+	pub config: Option<PathBuf>,
+	/// name of the layout mapping keyboard keys to steno keys (default: from the config, or qwerty)
+	#[argh(option, short = 'l')]
+	pub layout: Option<String>,
+	/// show translations that later strokes may still change as preedit, committing them once they are settled
+	#[argh(switch, short = 'e')]
+	pub preedit: bool,
+	/// delete with backspace on a virtual keyboard rather than through the input method, which many applications handle better
+	#[argh(switch)]
+	pub hybrid: bool,
+	/// how strokes are made: chord (send once all keys are released), first-up (send once any key is released, starting a new stroke with the keys still held), arpeggiate (press keys one at a time, then the send key), or timeout (press keys one at a time, then pause)
+	#[argh(option, short = 'm', default = "<_>::default()")]
+	pub stroke_mode: StrokeMode,
+	/// keycode that sends the stroke in arpeggiate mode, taking priority over the layout (default: 57, space)
+	#[argh(option, default = "DEFAULT_SEND_KEY")]
+	pub send_key: u32,
+	/// milliseconds without a key press after which the stroke is sent in timeout mode
+	#[argh(option, short = 't', default = "DEFAULT_STROKE_TIMEOUT")]
+	pub stroke_timeout: u64,
+	/// milliseconds that a stroke must be held before it's sent and repeated, in chord and first-up modes (default: no repeat)
+	#[argh(option)]
+	pub repeat_delay: Option<u64>,
+	/// milliseconds between repeats of a held stroke
+	#[argh(option, default = "DEFAULT_REPEAT_INTERVAL")]
+	pub repeat_interval: u64,
+	/// where the output goes: input-method, virtual-keyboard or stdout (default: input-method)
+	#[argh(option, short = 'o', default = "OutputKind::InputMethod")]
+	pub output: OutputKind,
+	/// path to a steno machine to also take strokes from; may be repeated
+	#[argh(option)]
+	pub machine: Vec<String>,
+	/// protocol used by the machines: gemini, tx-bolt, stentura or plover-hid (default: that of the known machine, or gemini)
+	#[argh(option)]
+	pub machine_protocol: Option<StenoProtocol>,
+	/// don't grab the keyboard, taking strokes only from machines
+	#[argh(switch)]
+	pub no_keyboard: bool,
+	/// path to record the strokes to as a session file, which `replay` runs again
+	#[argh(option)]
+	pub record: Option<PathBuf>,
+	/// also record the bytes read from steno machines in the session file
+	#[argh(switch)]
+	pub record_bytes: bool,
+	/// milliseconds to wait after each key with the virtual-keyboard output, for applications that drop keys
+	#[argh(option, default = "0")]
+	pub key_delay: u64,
+	/// milliseconds to wait between deleting and typing with the virtual-keyboard output
+	#[argh(option, default = "0")]
+	pub delete_delay: u64,
+	/// number of keys to send at once with the virtual-keyboard output when there's no key delay
+	#[argh(option, default = "DEFAULT_BATCH")]
+	pub batch: usize,
+	/// log every key event sent for each output with the virtual-keyboard output
+	#[argh(switch)]
+	pub debug_keys: bool,
+	/// paste outputs with at least this many characters through the clipboard with the virtual-keyboard output
+	#[argh(option)]
+	pub paste_threshold: Option<usize>,
+	/// paste outputs containing these characters through the clipboard with the virtual-keyboard output: non-ascii or newline; may be repeated
+	#[argh(option)]
+	pub paste_for: Vec<PasteFor>,
+	/// shortcut that pastes in the focused application: ctrl-v or ctrl-shift-v (default: ctrl-v)
+	#[argh(option, default = "<_>::default()")]
+	pub paste_shortcut: PasteShortcut,
+}
+
+impl InputMethodArgs {
+	pub fn keyboard(&self) -> KeyboardOptions {
+		KeyboardOptions {
+			key_delay: self.key_delay,
+			delete_delay: self.delete_delay,
+			batch: self.batch,
+			debug_keys: self.debug_keys,
+			paste_threshold: self.paste_threshold,
+			paste_for: self.paste_for.clone(),
+			paste_shortcut: self.paste_shortcut,
+		}
+	}
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum StrokeMode {
+	#[default]
+	Chord,
+	FirstUp,
+	Arpeggiate,
+	Timeout,
+}
+
+#[derive(Debug, Error)]
+#[error("unrecognized stroke mode; supported are: chord, first-up, arpeggiate, timeout")]
+pub struct StrokeModeFromStrError;
+
+impl FromStr for StrokeMode {
+	type Err = StrokeModeFromStrError;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		Ok(match s {
+			"chord" => Self::Chord,
+			"first-up" => Self::FirstUp,
+			"arpeggiate" => Self::Arpeggiate,
+			"timeout" => Self::Timeout,
+			_ => return Err(StrokeModeFromStrError),
+		})
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputKind {
+	InputMethod,
+	VirtualKeyboard,
+	Stdout,
+}
+
+#[derive(Debug, Error)]
+#[error("unrecognized output; supported are: input-method, virtual-keyboard, stdout")]
+pub struct OutputKindFromStrError;
+
+impl FromStr for OutputKind {
+	type Err = OutputKindFromStrError;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		Ok(match s {
+			"input-method" => Self::InputMethod,
+			"virtual-keyboard" => Self::VirtualKeyboard,
+			"stdout" => Self::Stdout,
+			_ => return Err(OutputKindFromStrError),
+		})
+	}
+}
 
-#[derive(Debug, Default)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, DeserializeFromStr)]
 pub enum StenoProtocol {
 	#[default]
 	Gemini,
+	TxBolt,
+	/// Stentura writers, polled for their realtime strokes.
+	Stentura,
+	/// The Plover HID protocol, read from a `/dev/hidraw*` device.
+	PloverHid,
 }
 
 #[derive(Debug, Error)]
-#[error("unrecognized steno protocol; supported are: gemini")]
+#[error("unrecognized steno protocol; supported are: gemini, tx-bolt, stentura, plover-hid")]
 pub struct StenoProtocolFromStrError;
 
 impl FromStr for StenoProtocol {
@@ -47,23 +195,321 @@ impl FromStr for StenoProtocol {
 	fn from_str(s: &str) -> Result<Self, Self::Err> {
 		Ok(match s {
 			"gemini" => Self::Gemini,
+			"tx-bolt" => Self::TxBolt,
+			"stentura" => Self::Stentura,
+			"plover-hid" => Self::PloverHid,
 			_ => return Err(StenoProtocolFromStrError),
 		})
 	}
 }
 
-/// Run as an virtual keyboard, taking input from a dedicated stenotype machine.
+/// Run as an virtual keyboard, taking input from dedicated stenotype machines.
 #[derive(FromArgs, Debug)]
 #[argh(subcommand, name = "virtual-keyboard")]
 pub struct VirtualKeyboardArgs {
-	/// path to the steno device in `/dev`
+	/// path to a steno device in `/dev`; may be repeated to take strokes from several at once (default: the known machine that's plugged in)
 	#[argh(option, short = 'd')]
-	pub device: Option<String>,
-	/// protocol used by the steno device
-	#[argh(option, short = 'p', default = "<_>::default()")]
+	pub device: Vec<String>,
+	/// protocol used by the steno devices: gemini, tx-bolt, stentura or plover-hid (default: that of the known machine, or gemini)
+	#[argh(option, short = 'p')]
+	pub protocol: Option<StenoProtocol>,
+	/// path to a config JSON, of which the known machines and serial settings are used
+	#[argh(option, short = 'c')]
+	pub config: Option<PathBuf>,
+	/// baud rate of the steno devices (default: from the config or known machine, or 9600)
+	#[argh(option)]
+	pub baud: Option<u32>,
+	/// parity of the steno devices: none, odd or even (default: from the config or known machine, or none)
+	#[argh(option)]
+	pub parity: Option<Parity>,
+	/// flow control of the steno devices: none, software or hardware (default: from the config or known machine, or none)
+	#[argh(option)]
+	pub flow_control: Option<FlowControl>,
+	/// seconds a steno device may send nothing before it's taken to have stalled and is opened again; for Stentura, how long it may go without responding (default: from the config or known machine, or none)
+	#[argh(option)]
+	pub inactivity_timeout: Option<u64>,
+	/// where the output goes: virtual-keyboard, input-method or stdout (default: virtual-keyboard)
+	#[argh(option, short = 'o', default = "OutputKind::VirtualKeyboard")]
+	pub output: OutputKind,
+	/// path to record the strokes to as a session file, which `replay` runs again
+	#[argh(option)]
+	pub record: Option<PathBuf>,
+	/// also record the bytes read from steno machines in the session file
+	#[argh(switch)]
+	pub record_bytes: bool,
+	/// milliseconds to wait after each key, for applications that drop keys
+	#[argh(option, default = "0")]
+	pub key_delay: u64,
+	/// milliseconds to wait between deleting and typing
+	#[argh(option, default = "0")]
+	pub delete_delay: u64,
+	/// number of keys to send at once when there's no key delay
+	#[argh(option, default = "DEFAULT_BATCH")]
+	pub batch: usize,
+	/// log every key event sent for each output
+	#[argh(switch)]
+	pub debug_keys: bool,
+	/// paste outputs with at least this many characters through the clipboard instead of typing them
+	#[argh(option)]
+	pub paste_threshold: Option<usize>,
+	/// paste outputs containing these characters through the clipboard: non-ascii or newline; may be repeated
+	#[argh(option)]
+	pub paste_for: Vec<PasteFor>,
+	/// shortcut that pastes in the focused application: ctrl-v or ctrl-shift-v (default: ctrl-v)
+	#[argh(option, default = "<_>::default()")]
+	pub paste_shortcut: PasteShortcut,
+}
+
+impl VirtualKeyboardArgs {
+	pub fn keyboard(&self) -> KeyboardOptions {
+		KeyboardOptions {
+			key_delay: self.key_delay,
+			delete_delay: self.delete_delay,
+			batch: self.batch,
+			debug_keys: self.debug_keys,
+			paste_threshold: self.paste_threshold,
+			paste_for: self.paste_for.clone(),
+			paste_shortcut: self.paste_shortcut,
+		}
+	}
+}
+
+const DEFAULT_BATCH: usize = 64;
+pub const DEFAULT_SEND_KEY: u32 = 57;
+pub const DEFAULT_STROKE_TIMEOUT: u64 = 300;
+pub const DEFAULT_REPEAT_INTERVAL: u64 = 100;
+
+/// How the virtual keyboard output types, as set by the options of either frontend.
+#[derive(Debug, Clone)]
+pub struct KeyboardOptions {
+	pub key_delay: u64,
+	pub delete_delay: u64,
+	pub batch: usize,
+	pub debug_keys: bool,
+	pub paste_threshold: Option<usize>,
+	pub paste_for: Vec<PasteFor>,
+	pub paste_shortcut: PasteShortcut,
+}
+
+impl Default for KeyboardOptions {
+	fn default() -> Self {
+		Self {
+			key_delay: 0,
+			delete_delay: 0,
+			batch: DEFAULT_BATCH,
+			debug_keys: false,
+			paste_threshold: None,
+			paste_for: Vec::new(),
+			paste_shortcut: PasteShortcut::default(),
+		}
+	}
+}
+
+impl KeyboardOptions {
+	/// The options that were changed from their defaults, to report them when nothing would use them.
+	pub fn changed(&self) -> Vec<&'static str> {
+		[
+			("--key-delay", self.key_delay != 0),
+			("--delete-delay", self.delete_delay != 0),
+			("--batch", self.batch != DEFAULT_BATCH),
+			("--debug-keys", self.debug_keys),
+			("--paste-threshold", self.paste_threshold.is_some()),
+			("--paste-for", !self.paste_for.is_empty()),
+			("--paste-shortcut", self.paste_shortcut != PasteShortcut::default()),
+		]
+		.into_iter()
+		.filter_map(|(name, changed)| changed.then_some(name))
+		.collect()
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, DeserializeFromStr)]
+pub enum Parity {
+	None,
+	Odd,
+	Even,
+}
+
+#[derive(Debug, Error)]
+#[error("unrecognized parity; supported are: none, odd, even")]
+pub struct ParityFromStrError;
+
+impl FromStr for Parity {
+	type Err = ParityFromStrError;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		Ok(match s {
+			"none" => Self::None,
+			"odd" => Self::Odd,
+			"even" => Self::Even,
+			_ => return Err(ParityFromStrError),
+		})
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, DeserializeFromStr)]
+pub enum FlowControl {
+	None,
+	/// XON/XOFF.
+	Software,
+	/// RTS/CTS.
+	Hardware,
+}
+
+#[derive(Debug, Error)]
+#[error("unrecognized flow control; supported are: none, software, hardware")]
+pub struct FlowControlFromStrError;
+
+impl FromStr for FlowControl {
+	type Err = FlowControlFromStrError;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		Ok(match s {
+			"none" => Self::None,
+			"software" => Self::Software,
+			"hardware" => Self::Hardware,
+			_ => return Err(FlowControlFromStrError),
+		})
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PasteFor {
+	NonAscii,
+	Newline,
+}
+
+#[derive(Debug, Error)]
+#[error("unrecognized paste rule; supported are: non-ascii, newline")]
+pub struct PasteForFromStrError;
+
+impl FromStr for PasteFor {
+	type Err = PasteForFromStrError;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		Ok(match s {
+			"non-ascii" => Self::NonAscii,
+			"newline" => Self::Newline,
+			_ => return Err(PasteForFromStrError),
+		})
+	}
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PasteShortcut {
+	#[default]
+	CtrlV,
+	/// For terminals, where Ctrl+V is passed on to the program running in them.
+	CtrlShiftV,
+}
+
+#[derive(Debug, Error)]
+#[error("unrecognized paste shortcut; supported are: ctrl-v, ctrl-shift-v")]
+pub struct PasteShortcutFromStrError;
+
+impl FromStr for PasteShortcut {
+	type Err = PasteShortcutFromStrError;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		Ok(match s {
+			"ctrl-v" => Self::CtrlV,
+			"ctrl-shift-v" => Self::CtrlShiftV,
+			_ => return Err(PasteShortcutFromStrError),
+		})
+	}
+}
+
+/// Compare two dictionaries by meaning, listing added, removed, and changed outlines.
+#[derive(FromArgs, Debug)]
+#[argh(subcommand, name = "diff")]
+pub struct DiffArgs {
+	/// the dictionary before the changes
+	#[argh(positional)]
+	pub old: PathBuf,
+	/// the dictionary after the changes
+	#[argh(positional)]
+	pub new: PathBuf,
+}
+
+/// Three-way merge two dictionaries that were derived from a common base.
+#[derive(FromArgs, Debug)]
+#[argh(subcommand, name = "merge")]
+pub struct MergeArgs {
+	/// the common ancestor of both dictionaries
+	#[argh(positional)]
+	pub base: PathBuf,
+	/// our version of the dictionary
+	#[argh(positional)]
+	pub ours: PathBuf,
+	/// their version of the dictionary
+	#[argh(positional)]
+	pub theirs: PathBuf,
+	/// where to write the merged dictionary; conflicts are written with conflict markers
+	#[argh(option, short = 'o')]
+	pub output: PathBuf,
+}
+
+/// Suggest free outlines for a new brief.
+#[derive(FromArgs, Debug)]
+#[argh(subcommand, name = "suggest")]
+pub struct SuggestArgs {
+	/// maximum number of suggestions to show
+	#[argh(option, short = 'n', default = "20")]
+	pub count: usize,
+	/// the word or phrase to brief
+	#[argh(positional)]
+	pub phrase: Vec<String>,
+}
+
+/// Explain what each stroke of an outline writes, and why.
+#[derive(FromArgs, Debug)]
+#[argh(subcommand, name = "explain")]
+pub struct ExplainArgs {
+	/// read the outlines as pseudo-steno, like "NAN" for TPHAPB, using the chord table
+	#[argh(switch, short = 's')]
+	pub pseudo: bool,
+	/// the outlines to explain
+	#[argh(positional)]
+	pub outlines: Vec<String>,
+}
+
+/// Emulate a steno machine on a pseudo-terminal, for testing without one.
+#[derive(FromArgs, Debug)]
+#[argh(subcommand, name = "emulate-machine")]
+pub struct EmulateMachineArgs {
+	/// protocol to send strokes with: gemini, tx-bolt, stentura or plover-hid (default: gemini)
+	#[argh(option, short = 'p', default = "StenoProtocol::Gemini")]
 	pub protocol: StenoProtocol,
+	/// milliseconds to wait after the machine is opened before the first stroke
+	#[argh(option, default = "0")]
+	pub start_delay: u64,
+	/// milliseconds to wait between strokes
+	#[argh(option, default = "200")]
+	pub interval: u64,
+	/// milliseconds to wait between the bytes of a TX Bolt stroke, or between pressing and releasing the keys of a Plover HID stroke
+	#[argh(option, default = "0")]
+	pub byte_delay: u64,
+	/// file with the strokes to send, separated by spaces, slashes or lines (default: read from stdin as they come)
+	#[argh(positional)]
+	pub strokes: Option<PathBuf>,
+}
+
+/// Run the strokes of a recorded session through the dictionaries, showing the output of each.
+#[derive(FromArgs, Debug)]
+#[argh(subcommand, name = "replay")]
+pub struct ReplayArgs {
+	/// also show the bytes read from steno machines, if they were recorded
+	#[argh(switch, short = 'b')]
+	pub bytes: bool,
+	/// the session file, as recorded with --record
+	#[argh(positional)]
+	pub session: PathBuf,
 }
 
 pub fn load() -> Args {
-	argh::from_env()
+	let mut args: Args = argh::from_env();
+	if args.dict.is_empty() {
+		args.dict.push("dict.json".into());
+	}
+	args
 }
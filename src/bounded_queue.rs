@@ -33,6 +33,10 @@ impl<T> BoundedQueue<T> {
 		self.inner.len()
 	}
 
+	pub fn is_empty(&self) -> bool {
+		self.inner.is_empty()
+	}
+
 	pub fn inner(&self) -> &VecDeque<T> {
 		&self.inner
 	}
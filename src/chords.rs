@@ -0,0 +1,254 @@
+use std::cmp::Reverse;
+use std::path::Path;
+use std::sync::LazyLock;
+
+use anyhow::Context as _;
+use serde::de::{MapAccess, Visitor};
+use serde::{Deserialize, Deserializer};
+use thiserror::Error;
+
+use crate::dict::Strokes;
+use crate::keys::{Key, Keys};
+
+/// A named group of keys, such as `TPH` for "N".
+///
+/// By convention, chords on the right bank have names starting with a dash.
+#[derive(Debug, Clone)]
+pub struct Chord {
+	pub name: Box<str>,
+	pub keys: Keys,
+}
+
+impl Chord {
+	/// Whether the name of the chord is just its keys, like "S" or "-F".
+	pub fn is_trivial(&self) -> bool {
+		self.keys.to_string().trim_start_matches('-') == self.bare_name()
+	}
+
+	/// The name without the dash marking the right bank.
+	fn bare_name(&self) -> &str {
+		self.name.trim_start_matches('-')
+	}
+
+	fn bank(&self) -> Bank {
+		let vowels = Key::A | Key::O | Key::Star | Key::E | Key::U;
+		if self.name.starts_with('-') {
+			Bank::Right
+		} else if (self.keys & !vowels).is_empty() {
+			Bank::Vowel
+		} else {
+			Bank::Left
+		}
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Bank {
+	Left,
+	Vowel,
+	Right,
+}
+
+/// Whether `new` can be written after `keys` in steno order.
+/// The star and number bar are allowed anywhere, as pseudo-steno often places them loosely.
+fn follows(keys: Keys, new: Keys) -> bool {
+	let unordered = Key::Star | Key::NumberBar;
+	let last = (keys & !unordered).into_iter().last();
+	let first = (new & !unordered).into_iter().next();
+	(keys & new).is_empty() && last.zip(first).is_none_or(|(last, first)| last < first)
+}
+
+#[derive(Debug, Error)]
+pub enum PseudoParseError {
+	#[error("empty stroke")]
+	Empty,
+	#[error("{0:?} is not a sequence of known chords in steno order")]
+	Unknown(Box<str>),
+}
+
+/// A table of chords, used to explain outlines in terms of the letters and sounds they write, and to read pseudo-steno.
+#[derive(Debug, Clone)]
+pub struct ChordTable {
+	/// Sorted from most keys to least, so that the largest chords are found first.
+	chords: Vec<Chord>,
+}
+
+impl<'de> Deserialize<'de> for ChordTable {
+	fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+	where
+		D: Deserializer<'de>,
+	{
+		struct MapVisitor {}
+
+		impl<'de> Visitor<'de> for MapVisitor {
+			type Value = ChordTable;
+
+			fn expecting(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+				formatter.write_str("a map from chord names to keys")
+			}
+
+			fn visit_map<A: MapAccess<'de>>(self, mut access: A) -> Result<Self::Value, A::Error> {
+				// A `Vec` rather than a map, because the order of the table decides between chords of the same size.
+				let mut chords = Vec::with_capacity(access.size_hint().unwrap_or(0));
+				while let Some((name, keys)) = access.next_entry::<Box<str>, Keys>()? {
+					chords.push(Chord { name, keys });
+				}
+				chords.sort_by_key(|chord| Reverse(chord.keys.bits().count_ones()));
+				Ok(ChordTable { chords })
+			}
+		}
+
+		let visitor = MapVisitor {};
+		deserializer.deserialize_map(visitor)
+	}
+}
+
+static BUILTIN: LazyLock<ChordTable> = LazyLock::new(|| {
+	serde_json::from_str(include_str!("../chords.json")).expect("built-in chord table is invalid")
+});
+
+impl ChordTable {
+	pub fn builtin() -> &'static Self {
+		&BUILTIN
+	}
+
+	pub fn load(path: &Path) -> anyhow::Result<Self> {
+		let raw = std::fs::read_to_string(path)
+			.with_context(|| format!("reading from {}", path.display()))?;
+		serde_json::from_str(&raw).context("deserializing chord table from JSON")
+	}
+
+	/// Loads the chord table at `path`, or the built-in one without a path.
+	pub fn load_or_builtin(path: Option<&Path>) -> anyhow::Result<Self> {
+		let Some(path) = path else { return Ok(Self::builtin().clone()); };
+		Self::load(path).with_context(|| format!("loading chord table from {}", path.display()))
+	}
+
+	pub fn chords(&self) -> &[Chord] {
+		&self.chords
+	}
+
+	/// Splits `keys` into chords, preferring larger chords, in steno order.
+	/// Keys that aren't covered by any chord in the table are returned as their own unnamed chords.
+	pub fn split(&self, mut keys: Keys) -> Vec<Chord> {
+		let mut ret = Vec::new();
+
+		for chord in &self.chords {
+			if !chord.keys.is_empty() && keys.remove(chord.keys) {
+				ret.push(chord.clone());
+			}
+		}
+
+		ret.extend(keys.into_iter().map(|key| Chord {
+			name: key.to_string().into(),
+			keys: key.into(),
+		}));
+
+		ret.sort_by_key(|chord| chord.keys.into_iter().next());
+		ret
+	}
+
+	/// Parses a stroke written in pseudo-steno, like "NAN" or "N-NCH", using the chord names in this table.
+	///
+	/// Chords on the right bank are written without their dash once a vowel or a dash has been seen.
+	/// Chords must come in steno order, and there must be at least one.
+	pub fn parse_pseudo(&self, raw: &str) -> Result<Keys, PseudoParseError> {
+		let keys = self
+			.parse_pseudo_from(raw, Keys::empty(), false)
+			.ok_or_else(|| PseudoParseError::Unknown(raw.into()))?;
+		if keys.is_empty() {
+			return Err(PseudoParseError::Empty);
+		}
+		Ok(keys)
+	}
+
+	/// Parses an outline written in pseudo-steno, with strokes separated by slashes.
+	pub fn parse_pseudo_strokes(&self, raw: &str) -> Result<Strokes, PseudoParseError> {
+		raw
+			.split('/')
+			.map(|part| self.parse_pseudo(part))
+			.collect::<Result<Vec<_>, _>>()
+			.map(Strokes)
+	}
+
+	fn parse_pseudo_from(&self, rest: &str, keys: Keys, right: bool) -> Option<Keys> {
+		if rest.is_empty() {
+			return Some(keys);
+		}
+
+		if let Some(rest) = rest.strip_prefix('-') {
+			// A dash separates the banks, as in "N-" or "-NCH", so it can't come after right-bank keys.
+			let right_bank = Key::F
+				| Key::R2 | Key::P2
+				| Key::B | Key::L
+				| Key::G | Key::T2
+				| Key::S2 | Key::D
+				| Key::Z;
+			return (keys & right_bank)
+				.is_empty()
+				.then(|| self.parse_pseudo_from(rest, keys, true))?;
+		}
+
+		let mut matches: Vec<&Chord> = self
+			.chords
+			.iter()
+			.filter(|chord| {
+				let bank_ok = match chord.bank() {
+					Bank::Left => !right,
+					Bank::Vowel => true,
+					Bank::Right => right,
+				};
+				bank_ok
+					&& !chord.bare_name().is_empty()
+					&& rest.starts_with(chord.bare_name())
+					&& follows(keys, chord.keys)
+			})
+			.collect();
+		// Longest names first, falling back to shorter ones if the rest doesn't parse.
+		matches.sort_by_key(|chord| Reverse(chord.bare_name().len()));
+
+		matches.into_iter().find_map(|chord| {
+			let rest = &rest[chord.bare_name().len()..];
+			let right = right || chord.bank() != Bank::Left;
+			self.parse_pseudo_from(rest, keys | chord.keys, right)
+		})
+	}
+}
+
+#[test]
+fn test_split() {
+	let split = |raw: &str| {
+		ChordTable::builtin()
+			.split(raw.parse().unwrap())
+			.into_iter()
+			.map(|chord| chord.name)
+			.collect::<Vec<_>>()
+	};
+	assert_eq!(split("TPHAPB"), ["N", "A", "-N"].map(Box::from));
+	assert_eq!(split("PWEUFRPB"), ["B", "I", "-NCH"].map(Box::from));
+	assert_eq!(split("SKWRAOUPL"), ["J", "EW", "-M"].map(Box::from));
+	assert_eq!(split("TEFT"), ["T", "E", "-F", "-T"].map(Box::from));
+}
+
+#[test]
+fn test_parse_pseudo() {
+	let parse = |raw: &str| ChordTable::builtin().parse_pseudo(raw).map(|keys| keys.to_string());
+	assert_eq!(parse("NAN").unwrap(), "TPHAPB");
+	assert_eq!(parse("N-NCH").unwrap(), "TPHFRPB");
+	assert_eq!(parse("N-").unwrap(), "TPH");
+	assert_eq!(parse("-NCH").unwrap(), "FRPB");
+	assert_eq!(parse("JEWM").unwrap(), "SKWRAOUPL");
+	assert_eq!(parse("CHAT").unwrap(), "KHAT");
+	// Raw keys still work, since they are chords of their own.
+	assert_eq!(parse("TPHAPB").unwrap(), "TPHAPB");
+	assert_eq!(parse("S-S").unwrap(), "SS");
+	assert_eq!(parse("NA-N").unwrap(), "TPHAPB");
+	// Out of steno order.
+	assert!(parse("-NCHN").is_err());
+	assert!(parse("AN-N").is_err());
+	assert!(parse("NN").is_err());
+	// Empty strokes.
+	assert!(parse("").is_err());
+	assert!(parse("-").is_err());
+	assert!(ChordTable::builtin().parse_pseudo_strokes("NA//-N").is_err());
+}
@@ -44,17 +44,20 @@ macro_rules! str_enum {
 
 str_enum! {
 #[description = "plover command"]
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum PloverCommand {
 	Backspace = "backspace",
 	Quit = "quit",
 	Reset = "reset",
+	Toggle = "toggle",
+	Suspend = "suspend",
+	Resume = "resume",
 }
 }
 
 str_enum! {
 #[description = "special punct"]
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum SpecialPunct {
 	Period = ".",
 	Comma = ",",
@@ -74,7 +77,7 @@ impl SpecialPunct {
 	}
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum Part {
 	Verbatim(Box<str>),
 	Suffix(Box<str>),
@@ -213,7 +216,7 @@ fn parse_special(out: &mut Vec<Part>, inner: &str) -> Result<(), ParseError> {
 
 	let suffix = if out
 		.last()
-		.map_or(false, |last| matches!(last, Part::SetSpace(false)))
+		.is_some_and(|last| matches!(last, Part::SetSpace(false)))
 	{
 		out.pop();
 		true
@@ -319,9 +322,48 @@ fn test_parse_entry() {
 	);
 }
 
-#[derive(Clone, Debug, PartialEq, Eq, DeserializeFromStr)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash, DeserializeFromStr)]
 pub struct Entry(pub Arc<[Part]>);
 
+impl Entry {
+	/// The Plover commands this entry runs, in order.
+	pub fn commands(&self) -> impl Iterator<Item = PloverCommand> + '_ {
+		self.0.iter().filter_map(|part| match part {
+			Part::PloverCommand(command) => Some(*command),
+			_ => None,
+		})
+	}
+
+	/// The text written by this entry, ignoring formatting and commands.
+	pub fn plain_text(&self) -> String {
+		let mut ret = String::new();
+		for part in &*self.0 {
+			match part {
+				Part::Verbatim(text) => {
+					if !ret.is_empty() {
+						ret.push(' ');
+					}
+					ret += text;
+				}
+				Part::Suffix(text) | Part::Glue(text) => ret += text,
+				Part::SpecialPunct(punct) => ret += punct.as_str(),
+				Part::SetCaps(..) | Part::SetSpace(..) | Part::CarryToNext | Part::PloverCommand(..) => {}
+			}
+		}
+		ret
+	}
+}
+
+#[test]
+fn test_plain_text() {
+	let plain_text = |raw: &str| raw.parse::<Entry>().unwrap().plain_text();
+	assert_eq!(plain_text("{^ing}"), "ing");
+	assert_eq!(plain_text("{>}{&e}"), "e");
+	assert_eq!(plain_text("as well as"), "as well as");
+	assert_eq!(plain_text("{.}"), ".");
+	assert_eq!(plain_text("{PLOVER:backspace}"), "");
+}
+
 impl From<Vec<Part>> for Entry {
 	fn from(parts: Vec<Part>) -> Self {
 		Self(parts.into())
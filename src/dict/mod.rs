@@ -1,15 +1,20 @@
 use std::collections::HashMap;
-use std::path::Path;
+use std::fmt::{Debug, Display};
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
 
 use anyhow::Context as _;
 use serde::de::{MapAccess, Visitor};
 use serde::{Deserialize, Deserializer};
 
-pub use self::entry::{Entry, Part as EntryPart, PloverCommand, SpecialPunct};
+pub use self::entry::{Entry, Part as EntryPart, PloverCommand};
+pub use self::source::{SourceDict, SourceEntry};
 pub use self::strokes::Strokes;
+use crate::chords::ChordTable;
 use crate::keys::Keys;
 
 mod entry;
+mod source;
 mod strokes;
 
 #[derive(Debug)]
@@ -18,15 +23,37 @@ pub struct Dict {
 	max_strokes: usize,
 }
 
-impl<'de> Deserialize<'de> for Dict {
+struct StrokesMap<V> {
+	map: HashMap<Strokes, V>,
+	max_strokes: usize,
+}
+
+impl<'de, V: Deserialize<'de> + Debug> Deserialize<'de> for StrokesMap<V> {
 	fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
 	where
 		D: Deserializer<'de>,
 	{
-		struct MapVisitor {}
+		Self::deserialize_with(deserializer, str::parse)
+	}
+}
+
+impl<'de, V: Deserialize<'de> + Debug> StrokesMap<V> {
+	/// Deserializes with the outlines parsed by `parse`, to support notations other than raw steno.
+	fn deserialize_with<D, F, E>(deserializer: D, parse: F) -> Result<Self, D::Error>
+	where
+		D: Deserializer<'de>,
+		F: Fn(&str) -> Result<Strokes, E>,
+		E: Display,
+	{
+		struct MapVisitor<F, V>(F, PhantomData<V>);
 
-		impl<'de> Visitor<'de> for MapVisitor {
-			type Value = Dict;
+		impl<'de, F, E, V> Visitor<'de> for MapVisitor<F, V>
+		where
+			F: Fn(&str) -> Result<Strokes, E>,
+			E: Display,
+			V: Deserialize<'de> + Debug,
+		{
+			type Value = StrokesMap<V>;
 
 			fn expecting(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
 				formatter.write_str("a string-to-string map")
@@ -37,7 +64,8 @@ impl<'de> Deserialize<'de> for Dict {
 
 				let mut max_strokes = 1;
 
-				while let Some((key, value)) = access.next_entry::<Strokes, Entry>()? {
+				while let Some((key, value)) = access.next_entry::<String, V>()? {
+					let key = (self.0)(&key).map_err(serde::de::Error::custom)?;
 					if let Some(old) = map.get(&key) {
 						return Err(serde::de::Error::custom(format!(
 							"overlap on {key}; prev was {old:?}, current is {value:?}"
@@ -47,19 +75,91 @@ impl<'de> Deserialize<'de> for Dict {
 					map.insert(key, value);
 				}
 
-				Ok(Dict { map, max_strokes })
+				Ok(StrokesMap { map, max_strokes })
 			}
 		}
 
-		let visitor = MapVisitor {};
+		let visitor = MapVisitor::<F, V>(parse, PhantomData);
 		deserializer.deserialize_map(visitor)
 	}
 }
 
+/// How the outlines in a dictionary file are written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Notation {
+	/// Raw steno keys, as Plover writes them, like "TPHAPB".
+	Raw,
+	/// Pseudo-steno according to the chord table, like "NAN".
+	Pseudo,
+}
+
+impl Notation {
+	/// Dictionaries named like `*.pseudo.json` are in pseudo-steno; anything else is raw.
+	pub fn of(path: &Path) -> Self {
+		let is_pseudo = path
+			.file_stem()
+			.and_then(|stem| Path::new(stem).extension())
+			.is_some_and(|extension| extension == "pseudo");
+		if is_pseudo {
+			Self::Pseudo
+		} else {
+			Self::Raw
+		}
+	}
+}
+
+fn load_map<V: for<'de> Deserialize<'de> + Debug>(
+	path: &Path,
+	chords: &ChordTable,
+) -> anyhow::Result<StrokesMap<V>> {
+	let raw = std::fs::read_to_string(path)
+		.with_context(|| format!("reading from {}", path.display()))?;
+	let mut deserializer = serde_json::Deserializer::from_str(&raw);
+	let map = match Notation::of(path) {
+		Notation::Raw => StrokesMap::deserialize(&mut deserializer),
+		Notation::Pseudo => {
+			StrokesMap::deserialize_with(&mut deserializer, |raw| chords.parse_pseudo_strokes(raw))
+		}
+	}
+	.and_then(|map| deserializer.end().map(|()| map))
+	.context("deserializing dictionary from JSON")?;
+	Ok(map)
+}
+
+impl<'de> Deserialize<'de> for Dict {
+	fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+	where
+		D: Deserializer<'de>,
+	{
+		let StrokesMap { map, max_strokes } = StrokesMap::deserialize(deserializer)?;
+		Ok(Self { map, max_strokes })
+	}
+}
+
 impl Dict {
-	pub fn load(path: &Path) -> anyhow::Result<Self> {
-		let raw = std::fs::read_to_string(path).with_context(|| format!("reading from {path:?}"))?;
-		serde_json::from_str(&raw).context("deserializing dictionary from JSON")
+	/// Loads the dictionary at `path`, reading pseudo-steno outlines with `chords`.
+	pub fn load(path: &Path, chords: &ChordTable) -> anyhow::Result<Self> {
+		let StrokesMap { map, max_strokes } = load_map(path, chords)?;
+		Ok(Self { map, max_strokes })
+	}
+
+	/// Loads several dictionaries into one, with earlier dictionaries taking priority.
+	pub fn load_stack(paths: &[PathBuf], chords: &ChordTable) -> anyhow::Result<Self> {
+		let mut ret = Self {
+			map: HashMap::new(),
+			max_strokes: 1,
+		};
+
+		for path in paths {
+			let dict = Self::load(path, chords)
+				.with_context(|| format!("loading dictionary from {}", path.display()))?;
+			ret.max_strokes = ret.max_strokes.max(dict.max_strokes);
+			for (strokes, entry) in dict.map {
+				ret.map.entry(strokes).or_insert(entry);
+			}
+		}
+
+		Ok(ret)
 	}
 
 	pub fn get(&self, keys: &[Keys]) -> Option<&Entry> {
@@ -70,3 +170,18 @@ impl Dict {
 		self.max_strokes
 	}
 }
+
+#[test]
+fn test_pseudo_notation() {
+	assert_eq!(Notation::of(Path::new("dict.json")), Notation::Raw);
+	assert_eq!(Notation::of(Path::new("theory/briefs.pseudo.json")), Notation::Pseudo);
+
+	let mut deserializer = serde_json::Deserializer::from_str(r#"{"NAN": "nan", "N-/-NCH": "nunch"}"#);
+	let StrokesMap::<Entry> { map, max_strokes } = StrokesMap::deserialize_with(&mut deserializer, |raw| {
+		ChordTable::builtin().parse_pseudo_strokes(raw)
+	})
+	.unwrap();
+	assert_eq!(max_strokes, 2);
+	assert!(map.contains_key(&"TPHAPB".parse::<Strokes>().unwrap()));
+	assert!(map.contains_key(&"TPH/-FRPB".parse::<Strokes>().unwrap()));
+}
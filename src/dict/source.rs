@@ -0,0 +1,77 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::str::FromStr;
+
+use serde::{Deserialize, Deserializer};
+use serde_with::DeserializeFromStr;
+
+use super::entry::ParseError;
+use super::{load_map, Entry, Strokes, StrokesMap};
+use crate::chords::ChordTable;
+use crate::keys::Keys;
+
+/// A translation along with the text it was parsed from.
+///
+/// Tools that report on or rewrite dictionaries use this so that their output matches what the user wrote, while comparisons still happen on the parsed `Entry`.
+#[derive(Debug, Clone, DeserializeFromStr)]
+pub struct SourceEntry {
+	pub raw: Box<str>,
+	pub entry: Entry,
+}
+
+impl FromStr for SourceEntry {
+	type Err = ParseError;
+
+	fn from_str(raw: &str) -> Result<Self, Self::Err> {
+		Ok(Self {
+			raw: raw.into(),
+			entry: raw.parse()?,
+		})
+	}
+}
+
+/// Compares by meaning only; the source text is ignored.
+impl PartialEq for SourceEntry {
+	fn eq(&self, other: &Self) -> bool {
+		self.entry == other.entry
+	}
+}
+
+impl Eq for SourceEntry {}
+
+/// A dictionary for tooling, which unlike `Dict` retains the source text of each translation.
+#[derive(Debug, Default)]
+pub struct SourceDict {
+	map: HashMap<Strokes, SourceEntry>,
+	max_strokes: usize,
+}
+
+impl<'de> Deserialize<'de> for SourceDict {
+	fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+	where
+		D: Deserializer<'de>,
+	{
+		let StrokesMap { map, max_strokes } = StrokesMap::deserialize(deserializer)?;
+		Ok(Self { map, max_strokes })
+	}
+}
+
+impl SourceDict {
+	/// Loads the dictionary at `path`, reading pseudo-steno outlines with `chords`.
+	pub fn load(path: &Path, chords: &ChordTable) -> anyhow::Result<Self> {
+		let StrokesMap { map, max_strokes } = load_map(path, chords)?;
+		Ok(Self { map, max_strokes })
+	}
+
+	pub fn get(&self, keys: &[Keys]) -> Option<&SourceEntry> {
+		self.map.get(keys)
+	}
+
+	pub fn max_strokes(&self) -> usize {
+		self.max_strokes
+	}
+
+	pub fn iter(&self) -> impl Iterator<Item = (&Strokes, &SourceEntry)> {
+		self.map.iter()
+	}
+}
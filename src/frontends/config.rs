@@ -0,0 +1,75 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use anyhow::{bail, Context as _};
+use serde::de::IgnoredAny;
+use serde::Deserialize;
+use sordahe::keys::Keys;
+
+use super::input_method::Settings as InputMethodSettings;
+use super::source::{KnownMachine, SerialOptions};
+
+/// How keys reach applications while translation is suspended.
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SuspendMode {
+	/// Keep the keyboard grab and type keys through a virtual keyboard as they come, while strokes can still resume.
+	#[default]
+	Forward,
+	/// Release the keyboard grab, so that no keys are seen and only focusing another text field resumes.
+	Release,
+}
+
+/// Settings for the frontends, loaded from a JSON file.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct Config {
+	/// A keycode that suspends or resumes translation. It is never typed.
+	pub toggle_key: Option<u32>,
+	/// A stroke that suspends or resumes translation, regardless of the dictionary.
+	/// While suspended, its keys are typed before it resumes.
+	pub toggle_stroke: Option<Keys>,
+	pub suspend: SuspendMode,
+	/// Steno machines to recognize when discovering them, which take priority over the built-in ones.
+	pub machines: Vec<KnownMachine>,
+	/// Settings for machines on serial ports, which take priority over those of known machines.
+	pub serial: SerialOptions,
+	/// Settings that only the input method uses, in the same object as the rest.
+	#[serde(flatten)]
+	pub input_method: InputMethodSettings,
+	/// Fields that nothing took, which are rejected on loading, since they're most likely typos.
+	#[allow(clippy::zero_sized_map_values /* Only their names are kept, and a set can't be flattened. */)]
+	#[serde(flatten)]
+	unknown: HashMap<String, IgnoredAny>,
+}
+
+impl Config {
+	pub fn load(path: &Path) -> anyhow::Result<Self> {
+		let raw = std::fs::read_to_string(path)
+			.with_context(|| format!("reading from {}", path.display()))?;
+		Self::parse(&raw)
+	}
+
+	/// Loads the config at `path`, or the default one without a path.
+	pub fn load_or_default(path: Option<&Path>) -> anyhow::Result<Self> {
+		let Some(path) = path else { return Ok(Self::default()); };
+		Self::load(path).with_context(|| format!("loading config from {}", path.display()))
+	}
+
+	fn parse(raw: &str) -> anyhow::Result<Self> {
+		let config: Self = serde_json::from_str(raw).context("deserializing config from JSON")?;
+		if let Some(name) = config.unknown.keys().min() {
+			bail!("unknown field {name:?} in config");
+		}
+		Ok(config)
+	}
+}
+
+#[test]
+fn test_config() {
+	let config = Config::parse(r#"{"toggle-stroke": "PHRO*PBLG", "unmapped": "pass-through"}"#).unwrap();
+	assert_eq!(config.toggle_stroke, Some("PHRO*PBLG".parse().unwrap()));
+	assert!(matches!(config.input_method.unmapped, super::input_method::Unmapped::PassThrough));
+
+	assert!(Config::parse(r#"{"toggle-strok": "PHRO*PBLG"}"#).is_err());
+}
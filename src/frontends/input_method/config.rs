@@ -0,0 +1,244 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::LazyLock;
+
+use anyhow::{anyhow, ensure};
+use serde::Deserialize;
+use sordahe::keys::Keys;
+use wayland_protocols::wp::text_input::zv3::client::zwp_text_input_v3::{
+	ContentHint, ContentPurpose,
+};
+
+
+/// What to do with keys that the layout doesn't map to any steno keys.
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Unmapped {
+	#[default]
+	Ignore,
+	/// Send the keys on to the focused application through a virtual keyboard.
+	PassThrough,
+}
+
+/// How strokes are handled in a text field.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum FieldMode {
+	/// Translate strokes with the dictionary.
+	#[default]
+	Translate,
+	/// Write each stroke's keys as they are, such as `STKPW`.
+	Raw,
+	/// Type keys as they are through a virtual keyboard, as if the input method wasn't there.
+	/// Without a virtual keyboard, this falls back to `Raw`.
+	PassThrough,
+}
+
+/// Settings for text fields with a particular content purpose.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Profile {
+	pub mode: FieldMode,
+	/// Whether to capitalize the start of sentences.
+	pub auto_caps: bool,
+	/// Whether to put spaces between words.
+	pub auto_space: bool,
+	/// Dictionaries to use instead of the ones given on the command line, with earlier dictionaries taking priority.
+	pub dictionaries: Option<Vec<PathBuf>>,
+}
+
+impl Default for Profile {
+	fn default() -> Self {
+		Self {
+			mode: FieldMode::Translate,
+			auto_caps: true,
+			auto_space: true,
+			dictionaries: None,
+		}
+	}
+}
+
+impl Profile {
+	/// Lays the settings given in the config over this profile, keeping the ones they leave out.
+	fn apply(&mut self, settings: &ProfileSettings) {
+		self.mode = settings.mode.unwrap_or(self.mode);
+		self.auto_caps = settings.auto_caps.unwrap_or(self.auto_caps);
+		self.auto_space = settings.auto_space.unwrap_or(self.auto_space);
+		if let Some(dictionaries) = &settings.dictionaries {
+			self.dictionaries = Some(dictionaries.clone());
+		}
+	}
+}
+
+/// The settings of a profile given in the config, where those left out keep their value from the built-in profile.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct ProfileSettings {
+	pub mode: Option<FieldMode>,
+	pub auto_caps: Option<bool>,
+	pub auto_space: Option<bool>,
+	pub dictionaries: Option<Vec<PathBuf>>,
+}
+
+/// The names of content purposes that profiles can be given for.
+const PURPOSES: &[&str] = &[
+	"normal", "alpha", "digits", "number", "phone", "url", "email", "name", "password", "pin", "date",
+	"time", "datetime", "terminal",
+];
+
+/// Finds the name of the profile for a text field.
+/// Fields with sensitive or hidden text use the `password` profile, whatever their purpose.
+pub fn profile_name(hint: ContentHint, purpose: ContentPurpose) -> &'static str {
+	if hint.intersects(ContentHint::SensitiveData | ContentHint::HiddenText)
+		&& purpose != ContentPurpose::Pin
+	{
+		return "password";
+	}
+
+	match purpose {
+		ContentPurpose::Alpha => "alpha",
+		ContentPurpose::Digits => "digits",
+		ContentPurpose::Number => "number",
+		ContentPurpose::Phone => "phone",
+		ContentPurpose::Url => "url",
+		ContentPurpose::Email => "email",
+		ContentPurpose::Name => "name",
+		ContentPurpose::Password => "password",
+		ContentPurpose::Pin => "pin",
+		ContentPurpose::Date => "date",
+		ContentPurpose::Time => "time",
+		ContentPurpose::Datetime => "datetime",
+		ContentPurpose::Terminal => "terminal",
+		_ => "normal",
+	}
+}
+
+fn builtin_profiles() -> HashMap<Box<str>, Profile> {
+	let sensitive = Profile {
+		mode: FieldMode::PassThrough,
+		..Profile::default()
+	};
+	let address = Profile {
+		auto_caps: false,
+		auto_space: false,
+		..Profile::default()
+	};
+	[
+		("password", sensitive.clone()),
+		("pin", sensitive),
+		("url", address.clone()),
+		("email", address),
+	]
+	.into_iter()
+	.map(|(name, profile)| (name.into(), profile))
+	.collect()
+}
+
+/// A mapping from evdev keycodes to the steno keys they press.
+///
+/// One physical key can press several steno keys, such as a combined vowel key.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(transparent)]
+pub struct Layout(HashMap<u32, Keys>);
+
+impl Layout {
+	pub fn get(&self, code: u32) -> Option<Keys> {
+		self.0.get(&code).copied()
+	}
+}
+
+const DEFAULT_LAYOUT: &str = "qwerty";
+
+static BUILTIN_LAYOUTS: LazyLock<HashMap<Box<str>, Layout>> = LazyLock::new(|| {
+	serde_json::from_str(include_str!("../../../layouts.json")).expect("built-in layouts are invalid")
+});
+
+/// Settings that only the input method uses, which are read from the same config as the shared ones.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct Settings {
+	/// The name of the layout to use, from `layouts` or the built-in presets.
+	pub layout: Option<Box<str>>,
+	/// Layouts in addition to the built-in presets, which they take priority over.
+	pub layouts: HashMap<Box<str>, Layout>,
+	pub unmapped: Unmapped,
+	/// Profiles for text fields by content purpose, which take priority over the built-in ones.
+	pub profiles: HashMap<Box<str>, ProfileSettings>,
+}
+
+impl Settings {
+	/// Finds the layout called `name`, falling back to the one chosen in the config, then to QWERTY.
+	pub fn layout(&self, name: Option<&str>) -> anyhow::Result<&Layout> {
+		let name = name.or(self.layout.as_deref()).unwrap_or(DEFAULT_LAYOUT);
+		self
+			.layouts
+			.get(name)
+			.or_else(|| BUILTIN_LAYOUTS.get(name))
+			.ok_or_else(|| {
+				let mut available: Vec<_> = self.layouts.keys().chain(BUILTIN_LAYOUTS.keys()).collect();
+				available.sort();
+				available.dedup();
+				anyhow!("unknown layout {name:?}; available are: {available:?}")
+			})
+	}
+
+	/// The profiles for each content purpose, with the built-in ones filled in.
+	/// Purposes without a profile use the default one.
+	pub fn profiles(&self) -> anyhow::Result<HashMap<Box<str>, Profile>> {
+		for name in self.profiles.keys() {
+			ensure!(
+				PURPOSES.contains(&&**name),
+				"unknown content purpose {name:?} for profile; available are: {PURPOSES:?}"
+			);
+		}
+
+		let mut profiles = builtin_profiles();
+		for (name, settings) in &self.profiles {
+			profiles.entry(name.clone()).or_default().apply(settings);
+		}
+		Ok(profiles)
+	}
+}
+
+#[test]
+fn test_layout() {
+	let config: Settings = serde_json::from_str(
+		r#"{"layout": "split", "layouts": {"split": {"57": "AO", "34": "*"}}, "unmapped": "pass-through"}"#,
+	)
+	.unwrap();
+	assert!(matches!(config.unmapped, Unmapped::PassThrough));
+
+	let layout = config.layout(None).unwrap();
+	assert_eq!(layout.get(57), Some(Keys::A | Keys::O));
+	assert_eq!(layout.get(30), None);
+
+	let qwerty = config.layout(Some("qwerty")).unwrap();
+	assert_eq!(qwerty.get(30), Some(Keys::S));
+	assert_eq!(qwerty.get(38), Some(Keys::S2));
+	assert_eq!(qwerty.get(5), Some(Keys::NUMBER_BAR));
+
+	assert!(config.layout(Some("dvorak")).is_err());
+}
+
+#[test]
+fn test_profiles() {
+	let config: Settings = serde_json::from_str(
+		r#"{"profiles": {"url": {"auto-space": false}, "terminal": {"mode": "raw"}}}"#,
+	)
+	.unwrap();
+	let profiles = config.profiles().unwrap();
+
+	// Settings left out keep their value from the built-in profile.
+	let url = &profiles[profile_name(ContentHint::empty(), ContentPurpose::Url)];
+	assert!(!url.auto_caps);
+	assert!(!url.auto_space);
+	assert_eq!(profiles["terminal"].mode, FieldMode::Raw);
+	assert!(profiles["terminal"].auto_caps);
+	assert_eq!(profiles["email"].mode, FieldMode::Translate);
+	assert!(!profiles["email"].auto_caps);
+
+	let sensitive = profile_name(ContentHint::SensitiveData, ContentPurpose::Normal);
+	assert_eq!(profiles[sensitive].mode, FieldMode::PassThrough);
+
+	let config: Settings = serde_json::from_str(r#"{"profiles": {"secret": {}}}"#).unwrap();
+	assert!(config.profiles().is_err());
+}
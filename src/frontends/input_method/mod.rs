@@ -0,0 +1,1244 @@
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::io::ErrorKind;
+use std::os::fd::AsRawFd;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, bail, ensure, Context as _};
+use nix::poll::{poll, PollFd, PollFlags};
+use sordahe::bounded_queue::BoundedQueue;
+use sordahe::chars_or_bytes::CharsOrBytes;
+use sordahe::chords::ChordTable;
+use sordahe::dict::{Dict, PloverCommand};
+use sordahe::keys::Keys;
+use sordahe::steno::{word_deletion_len, Output, SpecialAction, Steno};
+use wayland_client::protocol::wl_keyboard::KeyState;
+use wayland_client::protocol::wl_registry;
+use wayland_client::protocol::wl_seat::WlSeat;
+use wayland_client::backend::WaylandError;
+use wayland_client::{delegate_noop, Connection, Dispatch, EventQueue, QueueHandle, WEnum};
+use wayland_protocols::wp::text_input::zv3::client::zwp_text_input_v3::{
+	ChangeCause, ContentHint, ContentPurpose,
+};
+use wayland_protocols_misc::zwp_input_method_v2::client::zwp_input_method_keyboard_grab_v2::{
+	self, ZwpInputMethodKeyboardGrabV2,
+};
+use wayland_protocols_misc::zwp_input_method_v2::client::zwp_input_method_manager_v2::ZwpInputMethodManagerV2;
+use wayland_protocols_misc::zwp_input_method_v2::client::zwp_input_method_v2::{
+	self, ZwpInputMethodV2,
+};
+use wayland_protocols_misc::zwp_virtual_keyboard_v1::client::zwp_virtual_keyboard_manager_v1::ZwpVirtualKeyboardManagerV1;
+use wayland_protocols_misc::zwp_virtual_keyboard_v1::client::zwp_virtual_keyboard_v1::ZwpVirtualKeyboardV1;
+
+use self::config::{profile_name, FieldMode, Layout, Profile};
+pub use self::config::{Settings, Unmapped};
+use self::preedit::{Changes, Preedit};
+use self::surrounding::before_cursor;
+use super::config::{Config, SuspendMode};
+use super::session::Recorder;
+use super::sink::{self, OutputSink};
+use super::source::{Bytes, KnownMachines, Machine, Sources, Stroke};
+use super::virtual_keyboard::{type_strokes, VirtualKeyboard};
+use crate::args::{
+	InputMethodArgs, KeyboardOptions, OutputKind, StrokeMode, DEFAULT_REPEAT_INTERVAL,
+	DEFAULT_SEND_KEY, DEFAULT_STROKE_TIMEOUT,
+};
+
+mod config;
+mod preedit;
+mod surrounding;
+
+/// The input method's own settings, apart from where strokes come from and where the output goes.
+#[allow(clippy::struct_excessive_bools /* They're independent switches. */)]
+#[derive(Debug)]
+pub struct Options {
+	/// The name of the layout mapping keyboard keys to steno keys, or `None` for the one chosen in the config.
+	pub layout: Option<String>,
+	/// Set if translations that later strokes may still change are shown as preedit.
+	pub preedit: bool,
+	/// Set if deletions are made by pressing backspace on a virtual keyboard.
+	pub hybrid: bool,
+	pub stroke_mode: StrokeMode,
+	pub send_key: u32,
+	pub stroke_timeout: Duration,
+	pub repeat_delay: Option<Duration>,
+	pub repeat_interval: Duration,
+	/// Set if strokes come from the keyboard, rather than only from machines.
+	pub keyboard: bool,
+	/// How to type on a virtual keyboard if there's no input method to commit text through.
+	pub typing: KeyboardOptions,
+}
+
+impl Default for Options {
+	fn default() -> Self {
+		Self {
+			layout: None,
+			preedit: false,
+			hybrid: false,
+			stroke_mode: StrokeMode::default(),
+			send_key: DEFAULT_SEND_KEY,
+			stroke_timeout: Duration::from_millis(DEFAULT_STROKE_TIMEOUT),
+			repeat_delay: None,
+			repeat_interval: Duration::from_millis(DEFAULT_REPEAT_INTERVAL),
+			keyboard: true,
+			typing: KeyboardOptions::default(),
+		}
+	}
+}
+
+impl From<&InputMethodArgs> for Options {
+	fn from(args: &InputMethodArgs) -> Self {
+		Self {
+			layout: args.layout.clone(),
+			preedit: args.preedit,
+			hybrid: args.hybrid,
+			stroke_mode: args.stroke_mode,
+			send_key: args.send_key,
+			stroke_timeout: Duration::from_millis(args.stroke_timeout),
+			repeat_delay: args.repeat_delay.map(Duration::from_millis),
+			repeat_interval: Duration::from_millis(args.repeat_interval),
+			keyboard: !args.no_keyboard,
+			typing: args.keyboard(),
+		}
+	}
+}
+
+/// Changes to the state of the text field, which are applied together on `done`.
+#[derive(Debug, Default)]
+struct FieldUpdate {
+	activate: bool,
+	deactivate: bool,
+	/// The surrounding text and the cursor's byte offset into it.
+	surrounding: Option<(String, u32)>,
+	cause: Option<ChangeCause>,
+	content_type: Option<(ContentHint, ContentPurpose)>,
+}
+
+#[allow(clippy::struct_excessive_bools /* They track unrelated things. */)]
+#[derive(Debug)]
+pub struct App {
+	input: ZwpInputMethodV2,
+	handle: QueueHandle<Self>,
+	/// Set if strokes come from the keyboard, rather than only from machines.
+	keyboard: bool,
+	/// `None` while suspended with the grab released, or without the keyboard.
+	grab: Option<ZwpInputMethodKeyboardGrabV2>,
+	serial: u32,
+	should_exit: bool,
+	/// The error that made the app exit, if any.
+	failure: Option<anyhow::Error>,
+	keys_seen: Keys,
+	/// Keycodes that are held down, along with the steno keys they press.
+	held: Vec<(u32, Keys)>,
+
+	layout: Layout,
+	unmapped: Unmapped,
+	/// A virtual keyboard with the same keymap as the real one, for keys that are typed as they are.
+	forward: Option<ZwpVirtualKeyboardV1>,
+	/// Set if deletions are made by pressing backspace on `forward`.
+	hybrid: bool,
+	/// The real keyboard's modifiers: depressed, latched, locked and group.
+	modifiers: [u32; 4],
+	/// The time of the last key event, for keys pressed on `forward`.
+	key_time: u32,
+
+	toggle_key: Option<u32>,
+	toggle_stroke: Option<Keys>,
+	suspend_mode: SuspendMode,
+	suspended: bool,
+	/// Set if pending translations are shown as preedit.
+	preedit: Option<Preedit>,
+
+	incoming: FieldUpdate,
+	/// Set while a text field is focused. Strokes are ignored otherwise.
+	active: bool,
+	/// The text before the cursor, as last reported by the client and updated with what was typed since.
+	before_cursor: Option<String>,
+
+	profiles: HashMap<Box<str>, Profile>,
+	field_mode: FieldMode,
+	/// Set if the focused text field holds sensitive data, which shouldn't be logged.
+	sensitive: bool,
+	/// The dictionary stacks that aren't in use, by the profile dictionaries they were loaded from.
+	/// `None` is the stack given on the command line.
+	spare_dicts: HashMap<Option<Vec<PathBuf>>, Dict>,
+	dicts_in_use: Option<Vec<PathBuf>>,
+
+	stroke_mode: StrokeMode,
+	send_key: u32,
+	stroke_timeout: Duration,
+	/// Set if keys were pressed since the last stroke was sent.
+	unsent: bool,
+	repeat_delay: Option<Duration>,
+	repeat_interval: Duration,
+	/// When the stroke in progress is sent in timeout mode, or repeated while held.
+	deadline: Option<Instant>,
+
+	/// Where the output goes instead of the text field, if anywhere.
+	sink: Option<Box<dyn OutputSink>>,
+	/// Where strokes are recorded, if anywhere. Strokes in sensitive text fields aren't.
+	recorder: Option<Recorder>,
+	steno: Steno,
+	buffer: BoundedQueue<u8>,
+}
+
+impl App {
+	fn key_pressed(&mut self, code: u32, keys: Keys) {
+		self.keys_seen |= keys;
+		self.held.push((code, keys));
+		self.unsent = true;
+	}
+
+	/// Returns whether the key was held.
+	/// Releases of keys that were pressed before suspending or resuming are ignored.
+	fn release_held(&mut self, code: u32) -> bool {
+		let before = self.held.len();
+		self.held.retain(|&(held, _)| held != code);
+		self.held.len() < before
+	}
+
+	/// Returns the stroke if this was the last key held.
+	fn key_released(&mut self, code: u32) -> Option<Keys> {
+		if !self.release_held(code) {
+			return None;
+		}
+		self.deadline = None;
+
+		self.held.is_empty().then(|| self.take_stroke()).flatten()
+	}
+
+	/// Returns the stroke if any key was pressed since the last one, and starts a new stroke with the keys still held.
+	fn first_up_released(&mut self, code: u32) -> Option<Keys> {
+		if !self.release_held(code) {
+			return None;
+		}
+		self.deadline = None;
+
+		let stroke = self.take_stroke();
+		self.keys_seen = self.held.iter().fold(Keys::empty(), |keys, &(_, held)| keys | held);
+		stroke
+	}
+
+	fn take_stroke(&mut self) -> Option<Keys> {
+		self.deadline = None;
+		let keys = std::mem::take(&mut self.keys_seen);
+		(std::mem::take(&mut self.unsent) && !keys.is_empty()).then_some(keys)
+	}
+
+	/// Sends the held stroke again, and keeps repeating it until a key is released.
+	/// Releasing the keys doesn't send it once more.
+	fn repeat_stroke(&mut self) -> Option<Keys> {
+		self.unsent = false;
+		self.deadline = Some(Instant::now() + self.repeat_interval);
+		(!self.keys_seen.is_empty()).then_some(self.keys_seen)
+	}
+
+	/// Whether the key makes strokes, rather than being typed.
+	fn is_steno_key(&self, code: u32) -> bool {
+		self.layout.get(code).is_some()
+			|| (self.stroke_mode == StrokeMode::Arpeggiate && code == self.send_key)
+	}
+
+	/// Handles an event for a key where `is_steno_key` is true, returning the stroke if it's finished.
+	fn steno_key(&mut self, code: u32, key_state: KeyState) -> Option<Keys> {
+		if self.stroke_mode == StrokeMode::Arpeggiate && code == self.send_key {
+			return (key_state == KeyState::Pressed).then(|| self.take_stroke()).flatten();
+		}
+
+		let keys = self.layout.get(code)?;
+		match (key_state, self.stroke_mode) {
+			(KeyState::Pressed, mode) => {
+				self.key_pressed(code, keys);
+				let now = Instant::now();
+				self.deadline = match mode {
+					StrokeMode::Timeout => Some(now + self.stroke_timeout),
+					StrokeMode::Chord | StrokeMode::FirstUp => self.repeat_delay.map(|delay| now + delay),
+					StrokeMode::Arpeggiate => None,
+				};
+				None
+			}
+			(KeyState::Released, StrokeMode::Chord) => self.key_released(code),
+			(KeyState::Released, StrokeMode::FirstUp) => self.first_up_released(code),
+			(KeyState::Released, StrokeMode::Arpeggiate | StrokeMode::Timeout) => {
+				self.release_held(code);
+				None
+			}
+			_ => None,
+		}
+	}
+
+	/// How long until the stroke in progress is sent or repeated by timing out.
+	fn timeout(&self) -> Option<Duration> {
+		self.deadline.map(|deadline| deadline.saturating_duration_since(Instant::now()))
+	}
+
+	fn stroke_timed_out(&mut self) {
+		let stroke = match self.stroke_mode {
+			StrokeMode::Timeout => self.take_stroke(),
+			// Held strokes aren't repeated while suspended, because their keys are typed instead.
+			_ if self.suspended => {
+				self.deadline = None;
+				None
+			}
+			_ => self.repeat_stroke(),
+		};
+		let Some(stroke) = stroke else { return; };
+		if self.suspended {
+			self.suspended_stroke(stroke);
+		} else {
+			self.run_stroke(stroke, None);
+		}
+	}
+
+	/// Runs a stroke, recording it along with the bytes it was read from, if any, unless the text field is sensitive.
+	fn run_stroke(&mut self, keys: Keys, bytes: Option<&Bytes>) {
+		if !self.sensitive {
+			eprintln!("{keys:#}");
+		}
+		if Some(keys) == self.toggle_stroke {
+			self.suspend();
+			return;
+		}
+		// Other sinks don't need a text field, and applications that don't support input methods never focus one.
+		// Without one, strokes are only run for commands such as suspending or quitting.
+		if !self.active && self.sink.is_none() {
+			if let Err(action) = self.steno.run_keys(keys).map(|()| self.steno.flush()) {
+				self.run_output(Err(action));
+			}
+			return;
+		}
+		if self.field_mode != FieldMode::Translate {
+			self.delete_and_commit(0, CharsOrBytes::default(), keys.to_string());
+			self.input.commit(self.serial);
+			return;
+		}
+		record_stroke(self.recorder.as_ref(), self.sensitive, keys, bytes);
+		let output = self.steno.run_keys(keys).map(|()| self.steno.flush());
+		self.run_output(output);
+	}
+
+	/// Handles a stroke from a machine, which has no keys to type while suspended.
+	fn machine_stroke(&mut self, Stroke { keys, bytes }: Stroke) {
+		if !self.suspended {
+			self.run_stroke(keys, bytes.as_ref());
+		} else if self.is_resume(keys) {
+			self.resume();
+		}
+	}
+
+	fn forward_key(&self, time: u32, code: u32, key_state: KeyState) {
+		if let Some(forward) = &self.forward {
+			forward.key(time, code, key_state as u32);
+		}
+	}
+
+	fn is_resume(&self, keys: Keys) -> bool {
+		Some(keys) == self.toggle_stroke
+			|| self
+				.steno
+				.lookup(keys)
+				.entry
+				.commands()
+				.any(|command| matches!(command, PloverCommand::Toggle | PloverCommand::Resume))
+	}
+
+	/// Handles a key while suspended, typing it right away while still making strokes from the steno keys, in case they resume.
+	fn suspended_key(&mut self, time: u32, code: u32, key_state: KeyState) {
+		self.forward_key(time, code, key_state);
+		if !self.is_steno_key(code) {
+			return;
+		}
+		if let Some(stroke) = self.steno_key(code, key_state) {
+			self.suspended_stroke(stroke);
+		}
+	}
+
+	/// Resumes if the stroke is one that resumes, even though its keys have been typed like any others.
+	fn suspended_stroke(&mut self, stroke: Keys) {
+		if !self.is_resume(stroke) {
+			return;
+		}
+		// Keys still held won't have their releases forwarded once resumed, so release them now.
+		for &(code, _) in &self.held {
+			self.forward_key(self.key_time, code, KeyState::Released);
+		}
+		self.resume();
+	}
+
+	fn suspend(&mut self) {
+		if self.suspended {
+			return;
+		}
+		self.commit_preedit();
+		eprintln!("suspended");
+		self.suspended = true;
+		self.held.clear();
+		self.keys_seen = Keys::empty();
+		self.deadline = None;
+		if let SuspendMode::Release = self.suspend_mode {
+			if let Some(grab) = self.grab.take() {
+				grab.release();
+			}
+		}
+	}
+
+	fn resume(&mut self) {
+		if !self.suspended {
+			return;
+		}
+		eprintln!("resumed");
+		self.suspended = false;
+		self.held.clear();
+		self.keys_seen = Keys::empty();
+		self.deadline = None;
+		if self.keyboard && self.grab.is_none() {
+			self.grab = Some(self.input.grab_keyboard(&self.handle, ()));
+		}
+	}
+
+	/// Handles a key from the keyboard grab.
+	fn grabbed_key(&mut self, time: u32, code: u32, key_state: KeyState) {
+		self.key_time = time;
+		if Some(code) == self.toggle_key {
+			if key_state == KeyState::Pressed {
+				self.toggle();
+			}
+			return;
+		}
+
+		if self.suspended {
+			self.suspended_key(time, code, key_state);
+			return;
+		}
+
+		if self.field_mode == FieldMode::PassThrough {
+			self.commit_preedit();
+			self.forward_key(time, code, key_state);
+			return;
+		}
+
+		if code == ESCAPE_KEY {
+			self.commit_preedit();
+			self.should_exit = true;
+			return;
+		}
+
+		if !self.is_steno_key(code) {
+			if let Unmapped::PassThrough = self.unmapped {
+				self.commit_preedit();
+				self.forward_key(time, code, key_state);
+			}
+			return;
+		}
+
+		if let Some(stroke) = self.steno_key(code, key_state) {
+			self.run_stroke(stroke, None);
+		}
+	}
+
+	fn toggle(&mut self) {
+		if self.suspended {
+			self.resume();
+		} else {
+			self.suspend();
+		}
+	}
+
+	/// Deletes by pressing backspace on the virtual keyboard, holding control to delete words.
+	fn press_backspaces(&self, forward: &ZwpVirtualKeyboardV1, chars: usize, words: usize) {
+		let [depressed, latched, locked, group] = self.modifiers;
+		let backspaces = |count| {
+			for _ in 0..count {
+				forward.key(self.key_time, BACKSPACE_KEY, KeyState::Pressed as u32);
+				forward.key(self.key_time, BACKSPACE_KEY, KeyState::Released as u32);
+			}
+		};
+		backspaces(chars);
+		if words > 0 {
+			forward.modifiers(depressed | MOD_CONTROL, latched, locked, group);
+			backspaces(words);
+			forward.modifiers(depressed, latched, locked, group);
+		}
+	}
+
+	fn delete_and_commit(&mut self, delete_words: usize, delete: CharsOrBytes, commit: String) {
+		if let Some(sink) = &mut self.sink {
+			let output = Output {
+				delete_words,
+				delete,
+				append: commit,
+			};
+			if let Err(error) = sink.write(output) {
+				self.failure = Some(error);
+				self.should_exit = true;
+			}
+			return;
+		}
+
+		let before_words = self
+			.before_cursor
+			.as_deref()
+			.and_then(|before| before.get(..before.len().checked_sub(delete.bytes())?));
+		let delete_bytes = delete.bytes()
+			+ match before_words {
+				Some(before) => word_deletion_len(before, delete_words),
+				// Without the surrounding text, words can't be found, so we'll delete a single character for each instead.
+				None => delete_words,
+			};
+
+		if let Some(before) = &mut self.before_cursor {
+			match before.len().checked_sub(delete_bytes) {
+				Some(len) if before.is_char_boundary(len) => {
+					before.truncate(len);
+					*before += &commit;
+				}
+				_ => self.before_cursor = None,
+			}
+		}
+
+		if let Some(forward) = self.forward.as_ref().filter(|_| self.hybrid) {
+			self.press_backspaces(forward, delete.chars(), delete_words);
+		} else {
+			let delete = delete_bytes.try_into().expect("deletion overflowed u32");
+			self.input.delete_surrounding_text(delete, 0);
+		}
+		self.input.commit_string(commit);
+	}
+
+	fn use_dicts(&mut self, dicts: Option<&Vec<PathBuf>>) {
+		if self.dicts_in_use.as_ref() == dicts {
+			return;
+		}
+		let dicts = dicts.cloned();
+		let dict = self
+			.spare_dicts
+			.remove(&dicts)
+			.expect("dictionaries are loaded for every profile");
+		let old = self.steno.replace_dict(dict);
+		let old_dicts = std::mem::replace(&mut self.dicts_in_use, dicts);
+		self.spare_dicts.insert(old_dicts, old);
+	}
+
+	/// Sets up translation for a newly activated text field, by the profile for its content type.
+	fn apply_profile(&mut self, hint: ContentHint, purpose: ContentPurpose) {
+		let profile = self
+			.profiles
+			.get(profile_name(hint, purpose))
+			.cloned()
+			.unwrap_or_default();
+
+		self.field_mode = match profile.mode {
+			FieldMode::PassThrough if self.forward.is_none() => FieldMode::Raw,
+			mode => mode,
+		};
+		self.sensitive = hint.intersects(ContentHint::SensitiveData | ContentHint::HiddenText)
+			|| matches!(purpose, ContentPurpose::Password | ContentPurpose::Pin);
+		self.use_dicts(profile.dictionaries.as_ref());
+		self.steno.set_auto_formatting(profile.auto_caps, profile.auto_space);
+	}
+
+	/// Forgets everything written in the previous text field, so that undo can't reach into it.
+	/// This is done while the field is still active, so its preedit can be committed to it.
+	fn reset_field(&mut self) {
+		self.commit_preedit();
+		self.steno.reset();
+		self.buffer.clear();
+		self.before_cursor = None;
+	}
+
+	/// Applies the changes to the text field that the client sent since the last `done`.
+	fn update_field(&mut self) {
+		let FieldUpdate {
+			activate,
+			deactivate,
+			surrounding,
+			cause,
+			content_type,
+		} = std::mem::take(&mut self.incoming);
+
+		// With the grab released, nothing else can resume, so a newly focused text field does.
+		if activate && self.suspended && self.keyboard && self.grab.is_none() {
+			self.resume();
+		}
+
+		if activate || deactivate {
+			self.reset_field();
+			self.active = activate;
+		}
+		if activate {
+			let (hint, purpose) = content_type.unwrap_or((ContentHint::empty(), ContentPurpose::Normal));
+			self.apply_profile(hint, purpose);
+		}
+		let Some((text, cursor)) = surrounding else { return; };
+		let Some(before) = before_cursor(&text, cursor) else { return; };
+		let mut before = before.to_owned();
+
+		// Edits by the input method itself are already accounted for, and reports of them may lag behind.
+		let outside_edit = activate || cause == Some(ChangeCause::Other);
+		if outside_edit {
+			// The preedit is committed where the cursor is now, rather than lost to the edit.
+			before += self.preedit.as_ref().map_or("", Preedit::text);
+			self.commit_preedit();
+			self.steno.set_context(&before);
+			self.buffer.clear();
+		}
+		if outside_edit || self.before_cursor.is_none() {
+			self.before_cursor = Some(before);
+		}
+	}
+
+	fn send_preedit(&self) {
+		let Some(preedit) = &self.preedit else { return; };
+		let text = preedit.text();
+		let cursor = text.len().try_into().expect("preedit overflowed i32");
+		self.input.set_preedit_string(text.into(), cursor, cursor);
+	}
+
+	/// Commits all of the preedit, before anything else is typed or when translation stops.
+	fn commit_preedit(&mut self) {
+		let Some(preedit) = &mut self.preedit else { return; };
+		if preedit.text().is_empty() {
+			return;
+		}
+		let text = preedit.take();
+		self.input.commit_string(text);
+		self.send_preedit();
+		self.input.commit(self.serial);
+	}
+
+	fn run_output(&mut self, output: Result<Output, SpecialAction>) {
+		match output {
+			Ok(mut output) => {
+				output.use_buffer(&mut self.buffer);
+
+				if let Some(preedit) = &mut self.preedit {
+					let keep = self.steno.recent_text_len(self.steno.max_strokes());
+					let Changes {
+						delete,
+						delete_words,
+						commit,
+					} = preedit.apply(output, keep);
+					self.delete_and_commit(delete_words, delete, commit);
+					self.send_preedit();
+				} else {
+					let Output {
+						delete_words,
+						delete,
+						append,
+					} = output;
+					self.delete_and_commit(delete_words, delete, append);
+				}
+				self.input.commit(self.serial);
+			}
+			Err(SpecialAction::Quit) => {
+				self.commit_preedit();
+				self.should_exit = true;
+			}
+			Err(SpecialAction::Toggle) => self.toggle(),
+			Err(SpecialAction::Suspend) => self.suspend(),
+			Err(SpecialAction::Resume) => self.resume(),
+		}
+	}
+}
+
+const ESCAPE_KEY: u32 = 1;
+const BACKSPACE_KEY: u32 = 14;
+const MOD_CONTROL: u32 = 1 << 2;
+
+impl Dispatch<ZwpInputMethodKeyboardGrabV2, ()> for App {
+	fn event(
+		state: &mut Self,
+		_proxy: &ZwpInputMethodKeyboardGrabV2,
+		event: <ZwpInputMethodKeyboardGrabV2 as wayland_client::Proxy>::Event,
+		_data: &(),
+		_conn: &Connection,
+		_qhandle: &QueueHandle<Self>,
+	) {
+		match event {
+			zwp_input_method_keyboard_grab_v2::Event::Keymap {
+				format: WEnum::Value(format),
+				fd,
+				size,
+			} => {
+				// Forwarded keys are interpreted with the same keymap as the real keyboard.
+				if let Some(forward) = &state.forward {
+					forward.keymap(format as u32, fd.as_raw_fd(), size);
+				}
+			}
+			zwp_input_method_keyboard_grab_v2::Event::Modifiers {
+				mods_depressed,
+				mods_latched,
+				mods_locked,
+				group,
+				..
+			} => {
+				state.modifiers = [mods_depressed, mods_latched, mods_locked, group];
+				if let Some(forward) = &state.forward {
+					forward.modifiers(mods_depressed, mods_latched, mods_locked, group);
+				}
+			}
+			zwp_input_method_keyboard_grab_v2::Event::Key {
+				time,
+				key,
+				state: WEnum::Value(key_state),
+				..
+			} => state.grabbed_key(time, key, key_state),
+			_ => {}
+		}
+	}
+}
+
+impl Dispatch<ZwpInputMethodV2, ()> for App {
+	fn event(
+		state: &mut Self,
+		_proxy: &ZwpInputMethodV2,
+		event: <ZwpInputMethodV2 as wayland_client::Proxy>::Event,
+		_data: &(),
+		_conn: &Connection,
+		_qhandle: &QueueHandle<Self>,
+	) {
+		match event {
+			zwp_input_method_v2::Event::Activate => {
+				state.incoming = FieldUpdate {
+					activate: true,
+					..FieldUpdate::default()
+				};
+			}
+			zwp_input_method_v2::Event::Deactivate => {
+				state.incoming = FieldUpdate {
+					deactivate: true,
+					..FieldUpdate::default()
+				};
+			}
+			zwp_input_method_v2::Event::SurroundingText { text, cursor, .. } => {
+				state.incoming.surrounding = Some((text, cursor));
+			}
+			zwp_input_method_v2::Event::TextChangeCause {
+				cause: WEnum::Value(cause),
+			} => {
+				state.incoming.cause = Some(cause);
+			}
+			zwp_input_method_v2::Event::ContentType { hint, purpose } => {
+				let hint = match hint {
+					WEnum::Value(hint) => hint,
+					WEnum::Unknown(bits) => ContentHint::from_bits_truncate(bits),
+				};
+				let purpose = purpose.into_result().unwrap_or(ContentPurpose::Normal);
+				state.incoming.content_type = Some((hint, purpose));
+			}
+			zwp_input_method_v2::Event::Done => {
+				state.serial += 1;
+				state.update_field();
+			}
+			_ => {}
+		}
+	}
+}
+
+delegate_noop!(App: ZwpVirtualKeyboardV1);
+
+struct NeededProxies {
+	manager: Option<ZwpInputMethodManagerV2>,
+	virtual_keyboard_manager: Option<ZwpVirtualKeyboardManagerV1>,
+	seat: Option<WlSeat>,
+}
+
+const ZWP_INPUT_METHOD_MANAGER_V2_VERSION: u32 = 1;
+const ZWP_VIRTUAL_KEYBOARD_MANAGER_V1_VERSION: u32 = 1;
+const WL_SEAT_VERSION: u32 = 8;
+
+impl Dispatch<wl_registry::WlRegistry, ()> for NeededProxies {
+	fn event(
+		state: &mut Self,
+		registry: &wl_registry::WlRegistry,
+		event: wl_registry::Event,
+		(): &(),
+		_: &Connection,
+		handle: &QueueHandle<Self>,
+	) {
+		if let wl_registry::Event::Global {
+			name, interface, ..
+		} = event
+		{
+			match interface.as_str() {
+				"zwp_input_method_manager_v2" => {
+					let manager = registry.bind(name, ZWP_INPUT_METHOD_MANAGER_V2_VERSION, handle, ());
+					state.manager = Some(manager);
+				}
+				"zwp_virtual_keyboard_manager_v1" => {
+					let manager = registry.bind(name, ZWP_VIRTUAL_KEYBOARD_MANAGER_V1_VERSION, handle, ());
+					state.virtual_keyboard_manager = Some(manager);
+				}
+				"wl_seat" => {
+					let seat = registry.bind(name, WL_SEAT_VERSION, handle, ());
+					state.seat = Some(seat);
+				}
+				_ => {}
+			}
+		}
+	}
+}
+
+impl NeededProxies {
+	/// Finds the globals, of which only the seat is required.
+	fn find(
+		conn: &Connection,
+	) -> anyhow::Result<(
+		Option<ZwpInputMethodManagerV2>,
+		Option<ZwpVirtualKeyboardManagerV1>,
+		WlSeat,
+	)> {
+		let mut needed = Self {
+			manager: None,
+			virtual_keyboard_manager: None,
+			seat: None,
+		};
+
+		let mut queue = conn.new_event_queue::<Self>();
+		let handle = queue.handle();
+
+		conn.display().get_registry(&handle, ());
+
+		queue.roundtrip(&mut needed)?;
+
+		let seat = needed
+			.seat
+			.ok_or_else(|| anyhow!("no wl_seat found in registry"))?;
+		Ok((needed.manager, needed.virtual_keyboard_manager, seat))
+	}
+}
+
+delegate_noop!(NeededProxies: ignore WlSeat);
+delegate_noop!(NeededProxies: ignore ZwpInputMethodManagerV2);
+delegate_noop!(NeededProxies: ignore ZwpVirtualKeyboardManagerV1);
+
+struct CheckIfImeAvailable {
+	available: bool,
+}
+
+impl Dispatch<ZwpInputMethodV2, ()> for CheckIfImeAvailable {
+	fn event(
+		state: &mut Self,
+		_proxy: &ZwpInputMethodV2,
+		event: <ZwpInputMethodV2 as wayland_client::Proxy>::Event,
+		_data: &(),
+		_conn: &Connection,
+		_qhandle: &QueueHandle<Self>,
+	) {
+		if let zwp_input_method_v2::Event::Unavailable = event {
+			state.available = false;
+		}
+	}
+}
+
+/// Whether polling found `fd` ready to read, or closed.
+fn is_readable(fd: PollFd) -> bool {
+	fd.revents()
+		.is_some_and(|events| events.intersects(PollFlags::POLLIN | PollFlags::POLLHUP | PollFlags::POLLERR))
+}
+
+/// Dispatches events and runs strokes from the machines, or sends the stroke in progress if it times out first.
+fn dispatch(queue: &mut EventQueue<App>, app: &mut App, sources: &mut Sources) -> anyhow::Result<()> {
+	if queue.dispatch_pending(app)? > 0 {
+		return Ok(());
+	}
+	queue.flush()?;
+
+	let guard = queue.prepare_read()?;
+	let timeout_ms = app
+		.timeout()
+		.map_or(-1, |timeout| timeout.as_millis().try_into().unwrap_or(i32::MAX));
+	let mut fds = [
+		PollFd::new(guard.connection_fd().as_raw_fd(), PollFlags::POLLIN),
+		PollFd::new(sources.wake_fd(), PollFlags::POLLIN),
+	];
+	let ready = poll(&mut fds, timeout_ms).context("waiting for events")?;
+	if ready == 0 {
+		drop(guard);
+		app.stroke_timed_out();
+		return Ok(());
+	}
+
+	if is_readable(fds[0]) {
+		match guard.read() {
+			Ok(_) => {}
+			Err(WaylandError::Io(error)) if error.kind() == ErrorKind::WouldBlock => {}
+			Err(error) => return Err(error).context("reading events"),
+		}
+	} else {
+		drop(guard);
+	}
+
+	if is_readable(fds[1]) {
+		if let Some(stroke) = sources.recv()? {
+			app.machine_stroke(stroke);
+		} else if sources.is_empty() && !app.keyboard {
+			eprintln!("no machines are left to take strokes from");
+			app.should_exit = true;
+		}
+	}
+
+	queue.dispatch_pending(app)?;
+	Ok(())
+}
+
+/// Registers as the input method, or returns why it can't be: there's no manager, or another one already is registered.
+fn get_input_method(
+	conn: &Connection,
+	manager: Option<&ZwpInputMethodManagerV2>,
+	seat: &WlSeat,
+) -> anyhow::Result<Result<ZwpInputMethodV2, &'static str>> {
+	let Some(manager) = manager else {
+		return Ok(Err("no zwp_input_method_manager_v2 found in registry"));
+	};
+	let mut queue = conn.new_event_queue::<CheckIfImeAvailable>();
+	let handle = queue.handle();
+
+	let input = manager.get_input_method(seat, &handle, ());
+
+	let mut check = CheckIfImeAvailable { available: true };
+	queue.roundtrip(&mut check)?;
+	Ok(check.available.then_some(input).ok_or("an IME is already registered"))
+}
+
+/// Types the strokes from machines on a virtual keyboard, or `sink` if there is one, when the input method can't be registered.
+/// Strokes from the keyboard can't be taken without the input method's keyboard grab, so this fails without machines.
+fn without_input_method(
+	reason: &str,
+	steno: Steno,
+	options: &Options,
+	sources: Sources,
+	sink: Option<Box<dyn OutputSink>>,
+	recorder: Option<&Recorder>,
+) -> anyhow::Result<()> {
+	ensure!(
+		!sources.is_empty(),
+		"{reason}, and the keyboard can only be read through the input method's keyboard grab; take strokes from a steno machine with --machine instead"
+	);
+	if options.keyboard {
+		eprintln!("{reason}; taking strokes only from the machines, since the keyboard can only be read through the input method");
+	}
+	let mut sink = if let Some(sink) = sink {
+		sink
+	} else {
+		eprintln!("{reason}; typing on a virtual keyboard instead");
+		Box::new(VirtualKeyboard::connect(&options.typing)?)
+	};
+	type_strokes(steno, sources, sink.as_mut(), recorder)
+}
+
+/// Opens the sink for outputs other than the input method.
+/// Preedit is turned off for them, since only the input method can show it.
+fn open_sink(
+	output: OutputKind,
+	keyboard: &KeyboardOptions,
+	preedit: &mut bool,
+) -> anyhow::Result<Option<Box<dyn OutputSink>>> {
+	let sink = sink::open(output, keyboard)?;
+	if sink.is_some() && *preedit {
+		eprintln!("preedit is only shown with the input method output; committing translations right away instead");
+		*preedit = false;
+	}
+	Ok(sink)
+}
+
+/// Records a stroke and the bytes it was read from, unless it's in a sensitive text field, where neither may be kept.
+fn record_stroke(recorder: Option<&Recorder>, sensitive: bool, keys: Keys, bytes: Option<&Bytes>) {
+	if let Some(recorder) = recorder.filter(|_| !sensitive) {
+		recorder.stroke(keys, bytes);
+	}
+}
+
+/// Loads the dictionary stacks that profiles use instead of the one given on the command line.
+fn load_profile_dicts(
+	profiles: &HashMap<Box<str>, Profile>,
+	chords: &ChordTable,
+) -> anyhow::Result<HashMap<Option<Vec<PathBuf>>, Dict>> {
+	let mut spare_dicts = HashMap::new();
+	for dicts in profiles.values().filter_map(|profile| profile.dictionaries.as_ref()) {
+		if let Entry::Vacant(entry) = spare_dicts.entry(Some(dicts.clone())) {
+			let dict = Dict::load_stack(dicts, chords)
+				.with_context(|| format!("loading dictionaries for profile: {dicts:?}"))?;
+			entry.insert(dict);
+		}
+	}
+	Ok(spare_dicts)
+}
+
+pub fn run(steno: Steno, chords: &ChordTable, mut args: InputMethodArgs) -> anyhow::Result<()> {
+	let config = Config::load_or_default(args.config.as_deref())?;
+	let known = KnownMachines::new(&config.machines)?;
+	let recorder = args
+		.record
+		.as_deref()
+		.map(|path| Recorder::create(path, args.record_bytes))
+		.transpose()?;
+	let mut sources = Sources::new()?;
+	for path in &args.machine {
+		let machine = Machine::open(
+			Some(path.clone()),
+			args.machine_protocol,
+			config.serial,
+			known.clone(),
+			recorder.as_ref().is_some_and(Recorder::records_bytes),
+		)?;
+		sources.add(path.clone(), Box::new(machine))?;
+	}
+	let sink = open_sink(args.output, &args.keyboard(), &mut args.preedit)?;
+	run_with_sources(steno, &Options::from(&args), &config, chords, sources, sink, recorder)
+}
+
+/// Runs the input method with strokes from `sources`, as well as from the keyboard unless that's turned off.
+/// The output goes to `sink` instead of the text field if there is one, and strokes are recorded to `recorder` if there is one.
+/// Profile dictionaries in pseudo-steno are read with `chords`.
+pub fn run_with_sources(
+	steno: Steno,
+	options: &Options,
+	config: &Config,
+	chords: &ChordTable,
+	mut sources: Sources,
+	sink: Option<Box<dyn OutputSink>>,
+	recorder: Option<Recorder>,
+) -> anyhow::Result<()> {
+	let &Options {
+		ref layout,
+		preedit,
+		hybrid,
+		stroke_mode,
+		send_key,
+		stroke_timeout,
+		repeat_delay,
+		repeat_interval,
+		keyboard,
+		typing: _,
+	} = options;
+	ensure!(
+		keyboard || !sources.is_empty(),
+		"without the keyboard, at least one machine is needed to take strokes from"
+	);
+
+	let layout = config.input_method.layout(layout.as_deref())?.clone();
+	let profiles = config.input_method.profiles()?;
+
+	let spare_dicts = load_profile_dicts(&profiles, chords)?;
+
+	let conn = Connection::connect_to_env().context("connecting to Wayland server")?;
+	let (manager, virtual_keyboard_manager, seat) = NeededProxies::find(&conn)?;
+
+	let input = match get_input_method(&conn, manager.as_ref(), &seat)? {
+		Ok(input) => input,
+		Err(reason) => {
+			return without_input_method(reason, steno, options, sources, sink, recorder.as_ref());
+		}
+	};
+
+	let mut queue = conn.new_event_queue::<App>();
+	let handle = queue.handle();
+
+	let mut suspend_mode = config.suspend;
+	let forward = match virtual_keyboard_manager {
+		Some(manager) => Some(manager.create_virtual_keyboard(&seat, &handle, ())),
+		None if matches!(config.input_method.unmapped, Unmapped::PassThrough) => {
+			bail!("passing through unmapped keys requires zwp_virtual_keyboard_manager_v1, which was not found in registry");
+		}
+		None => {
+			if let SuspendMode::Forward = suspend_mode {
+				eprintln!("no zwp_virtual_keyboard_manager_v1 found in registry; the keyboard grab will be released while suspended");
+				suspend_mode = SuspendMode::Release;
+			}
+			if hybrid {
+				eprintln!("no zwp_virtual_keyboard_manager_v1 found in registry; deleting through the input method instead");
+			}
+			if profiles.values().any(|profile| profile.mode == FieldMode::PassThrough) {
+				eprintln!("no zwp_virtual_keyboard_manager_v1 found in registry; pass-through text fields will get raw strokes instead");
+			}
+			None
+		}
+	};
+
+	let grab = keyboard.then(|| input.grab_keyboard(&handle, ()));
+
+	let mut app = App {
+		input,
+		handle,
+		keyboard,
+		grab,
+		serial: 0,
+		should_exit: false,
+		failure: None,
+		keys_seen: Keys::empty(),
+		held: Vec::new(),
+
+		layout,
+		unmapped: config.input_method.unmapped,
+		forward,
+		hybrid,
+		modifiers: [0; 4],
+		key_time: 0,
+
+		toggle_key: config.toggle_key,
+		toggle_stroke: config.toggle_stroke,
+		suspend_mode,
+		suspended: false,
+		preedit: preedit.then(Preedit::default),
+
+		incoming: FieldUpdate::default(),
+		active: false,
+		before_cursor: None,
+
+		profiles,
+		field_mode: FieldMode::Translate,
+		sensitive: false,
+		spare_dicts,
+		dicts_in_use: None,
+
+		stroke_mode,
+		send_key,
+		stroke_timeout,
+		unsent: false,
+		repeat_delay,
+		repeat_interval,
+		deadline: None,
+
+		sink,
+		recorder,
+		steno,
+		buffer: BoundedQueue::new(100),
+	};
+
+	queue.roundtrip(&mut app)?;
+
+	while !app.should_exit {
+		dispatch(&mut queue, &mut app, &mut sources)?;
+	}
+
+	if let Some(grab) = app.grab.take() {
+		grab.release();
+	}
+	queue.roundtrip(&mut app)?;
+
+	app.failure.map_or(Ok(()), Err)
+}
+
+#[test]
+fn test_sensitive_strokes_are_not_recorded() {
+	use super::session::{self, Event, Record};
+	use super::source::StrokeSource;
+
+	/// A machine that sends each stroke as a single byte.
+	struct Machine(Vec<Keys>);
+
+	impl StrokeSource for Machine {
+		fn read_stroke(&mut self) -> anyhow::Result<Option<Keys>> {
+			Ok((!self.0.is_empty()).then(|| self.0.remove(0)))
+		}
+
+		fn take_bytes(&mut self) -> Option<Bytes> {
+			Some(Bytes {
+				source: "machine".into(),
+				data: vec![u8::try_from(self.0.len()).unwrap()],
+			})
+		}
+	}
+
+	let path = std::env::temp_dir().join(format!("sordahe-sensitive-{}.jsonl", std::process::id()));
+	let recorder = Recorder::create(&path, true).unwrap();
+	let mut sources = Sources::new().unwrap();
+	sources
+		.add("machine".into(), Box::new(Machine(vec![Keys::S, Keys::T])))
+		.unwrap();
+
+	for sensitive in [true, false] {
+		let Stroke { keys, bytes } = sources.recv().unwrap().unwrap();
+		record_stroke(Some(&recorder), sensitive, keys, bytes.as_ref());
+	}
+	let events = session::load(&path).unwrap();
+	std::fs::remove_file(&path).unwrap();
+
+	let records: Vec<_> = events.into_iter().map(|Event { record, .. }| record).collect();
+	assert_eq!(
+		records,
+		[
+			Record::Bytes {
+				source: "machine".into(),
+				data: vec![0],
+			},
+			Record::Stroke(Keys::T),
+		]
+	);
+}
+
+#[test]
+fn test_keyboard_strokes_suspend_and_resume() {
+	use std::os::unix::net::UnixStream;
+
+	use wayland_client::Proxy;
+
+	// Requests on inert proxies go nowhere, so no compositor is needed.
+	let conn = Connection::from_socket(UnixStream::pair().unwrap().0).unwrap();
+	let input = ZwpInputMethodV2::inert(conn.backend().downgrade());
+	let queue = conn.new_event_queue::<App>();
+	let dict = serde_json::from_str(r#"{"S": "{PLOVER:suspend}", "T": "{PLOVER:resume}"}"#).unwrap();
+	let word_list = include_str!("../../../words.txt").parse().unwrap();
+	let mut app = App {
+		input,
+		handle: queue.handle(),
+		keyboard: true,
+		grab: None,
+		serial: 0,
+		should_exit: false,
+		failure: None,
+		keys_seen: Keys::empty(),
+		held: Vec::new(),
+
+		layout: Settings::default().layout(None).unwrap().clone(),
+		unmapped: Unmapped::Ignore,
+		forward: None,
+		hybrid: false,
+		modifiers: [0; 4],
+		key_time: 0,
+
+		toggle_key: None,
+		toggle_stroke: None,
+		suspend_mode: SuspendMode::Forward,
+		suspended: false,
+		preedit: None,
+
+		incoming: FieldUpdate::default(),
+		active: false,
+		before_cursor: None,
+
+		profiles: HashMap::new(),
+		field_mode: FieldMode::Translate,
+		sensitive: false,
+		spare_dicts: HashMap::new(),
+		dicts_in_use: None,
+
+		stroke_mode: StrokeMode::Chord,
+		send_key: 57,
+		stroke_timeout: Duration::from_millis(300),
+		unsent: false,
+		repeat_delay: None,
+		repeat_interval: Duration::from_millis(100),
+		deadline: None,
+
+		sink: None,
+		recorder: None,
+		steno: Steno::new(dict, word_list),
+		buffer: BoundedQueue::new(100),
+	};
+	let stroke = |app: &mut App, code| {
+		app.grabbed_key(0, code, KeyState::Pressed);
+		app.grabbed_key(0, code, KeyState::Released);
+	};
+
+	// 16 and 17 are S- and T- on QWERTY.
+	stroke(&mut app, 16);
+	assert!(app.suspended);
+	// Strokes that don't resume are typed instead.
+	stroke(&mut app, 16);
+	assert!(app.suspended);
+	stroke(&mut app, 17);
+	assert!(!app.suspended);
+}
@@ -0,0 +1,159 @@
+use sordahe::chars_or_bytes::CharsOrBytes;
+use sordahe::steno::{word_deletion_len, Output};
+
+/// The end of the output, which the engine may still change, shown as preedit rather than committed.
+#[derive(Debug, Default)]
+pub struct Preedit {
+	text: String,
+}
+
+/// What to send to the client to apply an output.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Changes {
+	/// Committed text to delete before the cursor.
+	pub delete: CharsOrBytes,
+	/// Words of committed text to delete before the cursor.
+	pub delete_words: usize,
+	pub commit: String,
+}
+
+impl Preedit {
+	pub fn text(&self) -> &str {
+		&self.text
+	}
+
+	/// Takes all of the text, to commit it.
+	pub fn take(&mut self) -> String {
+		std::mem::take(&mut self.text)
+	}
+
+	/// Applies `output` to the preedit, deleting committed text only once the preedit is used up.
+	/// Characters are deleted before words, as everywhere else.
+	/// At most `keep` bytes are left as preedit, and only from the last word boundary on; the rest is committed.
+	pub fn apply(
+		&mut self,
+		Output {
+			delete_words,
+			delete,
+			append,
+		}: Output,
+		keep: usize,
+	) -> Changes {
+		let mut changes = Changes::default();
+
+		let from_preedit = delete.bytes().min(self.text.len());
+		let kept = self.text.len() - from_preedit;
+		changes.delete = delete - CharsOrBytes::for_str(&self.text[kept..]);
+		self.text.truncate(kept);
+
+		for _ in 0..delete_words {
+			if self.text.is_empty() {
+				changes.delete_words += 1;
+				continue;
+			}
+			let len = self.text.len() - word_deletion_len(&self.text, 1);
+			self.text.truncate(len);
+			// Committed text is only ever split off before whitespace or inside a long word,
+			// so a word that reaches the start of the preedit goes on in the committed text.
+			if self.text.is_empty() {
+				changes.delete_words += 1;
+			}
+		}
+
+		self.text += &append;
+
+		let mut settled = self
+			.text
+			.len()
+			.saturating_sub(keep)
+			.max(self.text.rfind(char::is_whitespace).unwrap_or(0));
+		while !self.text.is_char_boundary(settled) {
+			settled -= 1;
+		}
+		changes.commit = self.text.drain(..settled).collect();
+
+		changes
+	}
+}
+
+#[test]
+fn test_apply() {
+	let output = |delete: &str, append: &str| Output {
+		delete_words: 0,
+		delete: CharsOrBytes::for_str(delete),
+		append: append.into(),
+	};
+
+	let mut preedit = Preedit::default();
+
+	let changes = preedit.apply(output("", "Hell"), 100);
+	assert_eq!(changes, Changes::default());
+	assert_eq!(preedit.text(), "Hell");
+
+	// Rewriting the preedit doesn't touch committed text.
+	let changes = preedit.apply(output("ell", "ello"), 100);
+	assert_eq!(changes, Changes::default());
+	assert_eq!(preedit.text(), "Hello");
+
+	// Text before a word boundary is committed.
+	let changes = preedit.apply(output("", " world"), 100);
+	assert_eq!(changes.commit, "Hello");
+	assert_eq!(preedit.text(), " world");
+
+	// So is text beyond what the engine may still change.
+	let changes = preedit.apply(output("", "wide"), 4);
+	assert_eq!(changes.commit, " world");
+	assert_eq!(preedit.text(), "wide");
+
+	// Deleting more than the preedit deletes committed text.
+	let changes = preedit.apply(output("world wide", ""), 100);
+	assert_eq!(changes.delete, CharsOrBytes::for_str("world "));
+	assert_eq!(preedit.text(), "");
+}
+
+#[test]
+fn test_apply_deletions() {
+	/// Applies an output to the text it's written after, deleting characters, then words.
+	fn apply(text: &mut String, delete: usize, delete_words: usize, append: &str) {
+		let len = text.len() - delete;
+		let len = len - word_deletion_len(&text[..len], delete_words);
+		text.truncate(len);
+		*text += append;
+	}
+
+	let cases = [
+		// Within the preedit, keeping the space before the word.
+		(0, 1),
+		// Characters from the preedit, then words from either side of the boundary.
+		(2, 1),
+		(2, 2),
+		// Characters from both sides, then words from the committed text.
+		(8, 1),
+		(0, 3),
+	];
+	for (delete, delete_words) in cases {
+		let mut committed = "The quick".to_owned();
+		let mut preedit = Preedit::default();
+		let changes = preedit.apply(
+			Output {
+				delete_words: 0,
+				delete: CharsOrBytes::default(),
+				append: " brown fox".into(),
+			},
+			100,
+		);
+		committed += &changes.commit;
+		assert_eq!(preedit.text(), " fox");
+
+		let mut expected = "The quick brown fox".to_owned();
+		apply(&mut expected, delete, delete_words, "!");
+		let output = Output {
+			delete_words,
+			delete: CharsOrBytes::for_str(&"x".repeat(delete)),
+			append: "!".into(),
+		};
+		let changes = preedit.apply(output, 100);
+		apply(&mut committed, changes.delete.bytes(), changes.delete_words, &changes.commit);
+		assert_eq!(committed + preedit.text(), expected, "deleting {delete} and {delete_words} words");
+	}
+}
@@ -0,0 +1,4 @@
+/// The text before the cursor in `text`, if `cursor` is a valid byte offset into it.
+pub fn before_cursor(text: &str, cursor: u32) -> Option<&str> {
+	text.get(..usize::try_from(cursor).ok()?)
+}
@@ -1,2 +1,6 @@
+pub mod config;
 pub mod input_method;
+pub mod session;
+pub mod sink;
+pub mod source;
 pub mod virtual_keyboard;
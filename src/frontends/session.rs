@@ -0,0 +1,173 @@
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, LineWriter, Read, Write};
+use std::mem;
+use std::path::Path;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::Instant;
+
+use anyhow::Context as _;
+use serde::{Deserialize, Serialize};
+use sordahe::keys::Keys;
+
+use super::source::Bytes;
+
+/// Something that happened in a session, written as a line of JSON.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Event {
+	/// Milliseconds since recording started.
+	pub ms: u64,
+	#[serde(flatten)]
+	pub record: Record,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Record {
+	/// A stroke that was run by the steno engine.
+	Stroke(Keys),
+	/// Bytes read from a steno machine.
+	Bytes { source: String, data: Vec<u8> },
+}
+
+#[derive(Debug)]
+struct Session {
+	/// Set to `None` once writing fails, so that one failure doesn't flood the log.
+	file: Option<LineWriter<File>>,
+	start: Instant,
+	bytes: bool,
+}
+
+/// Records strokes, and optionally the bytes read from machines, to a session file that can be replayed.
+/// It's shared between the threads reading machines, and every event is written as soon as it happens.
+#[derive(Debug, Clone)]
+pub struct Recorder(Arc<Mutex<Session>>);
+
+impl Recorder {
+	/// Starts recording to a new file at `path`, recording the bytes read from machines too if `bytes`.
+	pub fn create(path: &Path, bytes: bool) -> anyhow::Result<Self> {
+		let file = File::create(path).with_context(|| format!("creating session at {}", path.display()))?;
+		Ok(Self(Arc::new(Mutex::new(Session {
+			file: Some(LineWriter::new(file)),
+			start: Instant::now(),
+			bytes,
+		}))))
+	}
+
+	/// Records a stroke, after the bytes it was read from if there are any.
+	pub fn stroke(&self, keys: Keys, bytes: Option<&Bytes>) {
+		if let Some(bytes) = bytes.filter(|bytes| !bytes.data.is_empty() && self.records_bytes()) {
+			self.record(Record::Bytes {
+				source: bytes.source.clone(),
+				data: bytes.data.clone(),
+			});
+		}
+		self.record(Record::Stroke(keys));
+	}
+
+	/// Whether the bytes read from machines should be captured to be recorded along with their strokes.
+	pub fn records_bytes(&self) -> bool {
+		self.0.lock().unwrap_or_else(PoisonError::into_inner).bytes
+	}
+
+	fn record(&self, record: Record) {
+		let mut session = self.0.lock().unwrap_or_else(PoisonError::into_inner);
+		let ms = u64::try_from(session.start.elapsed().as_millis()).unwrap_or(u64::MAX);
+		let Some(file) = &mut session.file else { return; };
+		let written = serde_json::to_writer(&mut *file, &Event { ms, record })
+			.map_err(io::Error::from)
+			.and_then(|()| writeln!(file));
+		if let Err(error) = written {
+			eprintln!("stopped recording the session after failing to write to it: {error}");
+			session.file = None;
+		}
+	}
+}
+
+/// Reads the events of a session, one per line.
+pub fn parse(input: impl BufRead) -> anyhow::Result<Vec<Event>> {
+	input
+		.lines()
+		.zip(1..)
+		.filter(|(line, _)| !line.as_ref().is_ok_and(|line| line.trim().is_empty()))
+		.map(|(line, number)| {
+			let line = line.context("reading session")?;
+			serde_json::from_str(&line).with_context(|| format!("parsing event on line {number}"))
+		})
+		.collect()
+}
+
+pub fn load(path: &Path) -> anyhow::Result<Vec<Event>> {
+	let file = File::open(path).with_context(|| format!("opening session at {}", path.display()))?;
+	parse(BufReader::new(file)).with_context(|| format!("loading session from {}", path.display()))
+}
+
+/// The bytes read through a `Tap` that haven't been taken yet.
+/// They're only recorded along with the stroke they make, once it's known not to be in a sensitive text field.
+#[derive(Debug, Clone, Default)]
+pub struct Captured(Arc<Mutex<Vec<u8>>>);
+
+impl Captured {
+	pub fn take(&self) -> Vec<u8> {
+		mem::take(&mut *self.0.lock().unwrap_or_else(PoisonError::into_inner))
+	}
+}
+
+/// A connection to a steno machine, capturing the bytes read from it.
+#[derive(Debug)]
+pub struct Tap<I> {
+	inner: I,
+	captured: Option<Captured>,
+}
+
+impl<I> Tap<I> {
+	pub fn new(inner: I, captured: Option<&Captured>) -> Self {
+		Self {
+			inner,
+			captured: captured.cloned(),
+		}
+	}
+}
+
+impl<I: Read> Read for Tap<I> {
+	fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+		let len = self.inner.read(buf)?;
+		if let Some(Captured(captured)) = &self.captured {
+			captured
+				.lock()
+				.unwrap_or_else(PoisonError::into_inner)
+				.extend_from_slice(&buf[..len]);
+		}
+		Ok(len)
+	}
+}
+
+impl<I: Write> Write for Tap<I> {
+	fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+		self.inner.write(buf)
+	}
+
+	fn flush(&mut self) -> io::Result<()> {
+		self.inner.flush()
+	}
+}
+
+#[test]
+fn test_session() {
+	let events = [
+		Event {
+			ms: 0,
+			record: Record::Bytes {
+				source: "/dev/ttyACM0".into(),
+				data: vec![0x80, 0x08, 0x20, 0x00, 0x04, 0x00],
+			},
+		},
+		Event {
+			ms: 1,
+			record: Record::Stroke("KAT".parse().unwrap()),
+		},
+	];
+	let lines: Vec<_> = events.iter().map(|event| serde_json::to_string(event).unwrap()).collect();
+	assert_eq!(lines[1], r#"{"ms":1,"stroke":"KAT"}"#);
+	assert_eq!(parse(lines.join("\n\n").as_bytes()).unwrap(), events);
+	assert!(parse(&b"{\"ms\":1,\"stroke\":\"Q\"}"[..]).is_err());
+}
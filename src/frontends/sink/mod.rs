@@ -0,0 +1,112 @@
+use std::fmt::Debug;
+use std::io::{self, Write};
+
+use anyhow::{ensure, Context as _};
+use sordahe::steno::{word_deletion_len, Output};
+
+use super::virtual_keyboard::VirtualKeyboard;
+use crate::args::{KeyboardOptions, OutputKind};
+
+/// Somewhere the output of the steno engine is written, other than the input method's text field.
+pub trait OutputSink: Debug {
+	/// Writes an output, deleting first.
+	fn write(&mut self, output: Output) -> anyhow::Result<()>;
+}
+
+/// Opens the sink for `kind`, returning `None` for the input method, which is driven by its own event loop.
+/// `keyboard` configures the virtual keyboard, and is an error to change for any other output.
+pub fn open(kind: OutputKind, keyboard: &KeyboardOptions) -> anyhow::Result<Option<Box<dyn OutputSink>>> {
+	if kind != OutputKind::VirtualKeyboard {
+		let ignored = keyboard.changed();
+		ensure!(
+			ignored.is_empty(),
+			"{} only apply to the virtual-keyboard output",
+			ignored.join(", ")
+		);
+	}
+	Ok(match kind {
+		OutputKind::InputMethod => None,
+		OutputKind::VirtualKeyboard => Some(Box::new(VirtualKeyboard::connect(keyboard)?)),
+		OutputKind::Stdout => Some(Box::<Stdout>::default()),
+	})
+}
+
+/// The most text that `Stdout` keeps to find words in.
+const MAX_WRITTEN: usize = 1024;
+
+/// Writes to standard output, or another writer, deleting with backspace characters that a terminal erases.
+#[derive(Debug)]
+pub struct Stdout<W = io::Stdout> {
+	/// The end of what was written, for word deletions.
+	written: String,
+	out: W,
+}
+
+impl Default for Stdout {
+	fn default() -> Self {
+		Self::new(io::stdout())
+	}
+}
+
+impl<W> Stdout<W> {
+	pub fn new(out: W) -> Self {
+		Self {
+			written: String::new(),
+			out,
+		}
+	}
+
+	/// Forgets what was deleted, returning the number of characters that were.
+	fn forget(&mut self, delete_words: usize, delete: usize) -> usize {
+		let Some(before) = self.written.len().checked_sub(delete) else {
+			self.written.clear();
+			return delete_words;
+		};
+		let Some(before) = self.written.get(..before) else {
+			self.written.clear();
+			return delete_words;
+		};
+		let len = before.len() - word_deletion_len(before, delete_words);
+		let words = self.written[len..before.len()].chars().count();
+		self.written.truncate(len);
+		words
+	}
+}
+
+impl<W: Write + Debug> OutputSink for Stdout<W> {
+	fn write(&mut self, output: Output) -> anyhow::Result<()> {
+		let Output {
+			delete_words,
+			delete,
+			append,
+		} = output;
+
+		let erase = delete.chars() + self.forget(delete_words, delete.bytes());
+		self.written += &append;
+		if self.written.len() > MAX_WRITTEN {
+			let start = self.written.ceil_char_boundary(self.written.len() - MAX_WRITTEN);
+			self.written.drain(..start);
+		}
+
+		let written = (0..erase)
+			.try_for_each(|_| self.out.write_all(b"\x08 \x08"))
+			.and_then(|()| self.out.write_all(append.as_bytes()))
+			.and_then(|()| self.out.flush());
+		written.context("writing to stdout")
+	}
+}
+
+#[test]
+fn test_stdout_forget() {
+	let mut stdout = Stdout {
+		written: "Hello, world".into(),
+		out: io::sink(),
+	};
+	assert_eq!(stdout.forget(1, 0), "world".len());
+	assert_eq!(stdout.written, "Hello, ");
+	assert_eq!(stdout.forget(0, 2), 0);
+	assert_eq!(stdout.written, "Hello");
+	// Deletions beyond what was kept can't be measured, so each word is a character.
+	assert_eq!(stdout.forget(2, 10), 2);
+	assert_eq!(stdout.written, "");
+}
@@ -0,0 +1,238 @@
+use std::fs::OpenOptions;
+use std::io::Write as _;
+use std::os::fd::AsRawFd;
+use std::os::unix::fs::OpenOptionsExt as _;
+use std::sync::mpsc;
+use std::time::Duration;
+
+use anyhow::{anyhow, Context as _};
+use nix::fcntl::OFlag;
+use nix::poll::{poll, PollFd, PollFlags};
+use nix::pty::{grantpt, posix_openpt, ptsname_r, unlockpt, PtyMaster};
+use nix::sys::termios::{cfmakeraw, tcgetattr, tcsetattr, SetArg};
+use sordahe::keys::Keys;
+
+use super::{gemini, plover_hid, stentura, tx_bolt};
+use crate::args::StenoProtocol;
+
+/// How often to check whether the emulated machine has been opened.
+const OPEN_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// When an emulated machine sends its strokes.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Timing {
+	/// How long to wait after the machine is opened before the first stroke.
+	pub start_delay: Duration,
+	/// How long to wait between strokes.
+	pub interval: Duration,
+	/// How long to wait between the writes that make up a stroke, such as the bytes of a TX Bolt stroke.
+	pub byte_delay: Duration,
+}
+
+impl Timing {
+	/// Calls `send` with each stroke once it's due.
+	fn pace(
+		self,
+		strokes: impl IntoIterator<Item = anyhow::Result<Keys>>,
+		mut send: impl FnMut(Keys) -> anyhow::Result<()>,
+	) -> anyhow::Result<()> {
+		std::thread::sleep(self.start_delay);
+		for (i, keys) in strokes.into_iter().enumerate() {
+			if i > 0 {
+				std::thread::sleep(self.interval);
+			}
+			send(keys?)?;
+		}
+		Ok(())
+	}
+}
+
+/// A steno machine emulated on a pseudo-terminal, which is opened like a real one through the path of its other end.
+#[derive(Debug)]
+pub struct Emulator {
+	master: PtyMaster,
+	path: String,
+}
+
+impl Emulator {
+	pub fn new() -> anyhow::Result<Self> {
+		let master = posix_openpt(OFlag::O_RDWR | OFlag::O_NOCTTY).context("opening pseudo-terminal")?;
+		grantpt(&master).context("granting pseudo-terminal")?;
+		unlockpt(&master).context("unlocking pseudo-terminal")?;
+		let path = ptsname_r(&master).context("getting pseudo-terminal path")?;
+
+		// Bytes have to go through untouched even if they're written before the machine is opened.
+		let mut termios = tcgetattr(master.as_raw_fd()).context("getting terminal attributes")?;
+		cfmakeraw(&mut termios);
+		tcsetattr(master.as_raw_fd(), SetArg::TCSANOW, &termios).context("setting terminal attributes")?;
+		// The pseudo-terminal only hangs up once its other end has been closed, so open and close it to tell when it's opened.
+		OpenOptions::new()
+			.read(true)
+			.write(true)
+			.custom_flags(OFlag::O_NOCTTY.bits())
+			.open(&path)
+			.with_context(|| format!("opening {path}"))?;
+
+		Ok(Self { master, path })
+	}
+
+	/// The path to open the machine at.
+	pub fn path(&self) -> &str {
+		&self.path
+	}
+
+	/// Whether nothing has the machine open, waiting until that's so if `block`.
+	fn is_closed(&self, block: bool) -> anyhow::Result<bool> {
+		let mut fds = [PollFd::new(self.master.as_raw_fd(), PollFlags::empty())];
+		poll(&mut fds, if block { -1 } else { 0 }).context("polling pseudo-terminal")?;
+		Ok(fds[0].revents().is_some_and(|events| events.contains(PollFlags::POLLHUP)))
+	}
+
+	/// Waits for the machine to be opened, then sends `strokes` with `protocol` and returns once it's closed.
+	pub fn run(
+		mut self,
+		protocol: StenoProtocol,
+		timing: Timing,
+		strokes: impl IntoIterator<Item = anyhow::Result<Keys>> + Send + 'static,
+	) -> anyhow::Result<()> {
+		while self.is_closed(false)? {
+			std::thread::sleep(OPEN_POLL_INTERVAL);
+		}
+
+		let writes: fn(Keys) -> Vec<Vec<u8>> = match protocol {
+			StenoProtocol::Gemini => |keys| vec![gemini::encode(keys).to_vec()],
+			StenoProtocol::TxBolt => |keys| tx_bolt::encode(keys).into_iter().map(|byte| vec![byte]).collect(),
+			StenoProtocol::PloverHid => |keys| {
+				vec![
+					plover_hid::report(keys).to_vec(),
+					plover_hid::report(Keys::empty()).to_vec(),
+				]
+			},
+			// Stentura writers only send strokes when they're asked for them.
+			StenoProtocol::Stentura => {
+				let (sender, receiver) = mpsc::channel();
+				std::thread::spawn(move || {
+					let sent = timing.pace(strokes, |keys| {
+						sender.send(Ok(keys)).map_err(|_| anyhow!("the machine was closed"))
+					});
+					if let Err(error) = sent {
+						// Nothing is left to report it to if the machine was closed.
+						let _ = sender.send(Err(error));
+					}
+				});
+				return stentura::serve(&mut self.master, &receiver);
+			}
+		};
+
+		timing.pace(strokes, |keys| {
+			for (i, bytes) in writes(keys).iter().enumerate() {
+				if i > 0 {
+					std::thread::sleep(timing.byte_delay);
+				}
+				self.master.write_all(bytes).context("writing stroke")?;
+			}
+			Ok(())
+		})?;
+		// Closing the pseudo-terminal would hang it up, so wait for the strokes to be read first.
+		self.is_closed(true)?;
+		Ok(())
+	}
+}
+
+#[test]
+fn test_emulator() {
+	use super::{open_machine, SerialOptions};
+
+	let strokes: Vec<Keys> = ["KAT", "TEFT", "S-Z", "#STKPWHRAO*EUFRPBLGTSDZ", "-Z", "E", "*"]
+		.iter()
+		.map(|stroke| stroke.parse().unwrap())
+		.collect();
+	for protocol in [
+		StenoProtocol::Gemini,
+		StenoProtocol::TxBolt,
+		StenoProtocol::Stentura,
+		StenoProtocol::PloverHid,
+	] {
+		let emulator = Emulator::new().unwrap();
+		let path = emulator.path().to_owned();
+		let sent = strokes.clone().into_iter().map(Ok);
+		let emulating = std::thread::spawn(move || emulator.run(protocol, Timing::default(), sent));
+
+		let settings = SerialOptions::default().settings();
+		let mut device = open_machine(&path, protocol, &settings, None).unwrap();
+		let received: Vec<_> = (0..strokes.len())
+			.map(|_| device.read_stroke().unwrap().unwrap())
+			.collect();
+		assert_eq!(received, strokes, "decoded with {protocol:?}");
+
+		drop(device);
+		emulating.join().unwrap().unwrap();
+	}
+}
+
+#[test]
+fn test_emulated_machine_reconnects() {
+	use std::os::unix::fs::symlink;
+	use std::path::Path;
+
+	use sordahe::steno::Steno;
+
+	use super::{KnownMachines, Machine, SerialOptions, Sources, Stroke};
+	use crate::frontends::sink::{OutputSink, Stdout};
+
+	type Running = std::thread::JoinHandle<anyhow::Result<()>>;
+
+	/// Emulates a machine at `link` that sends the strokes sent to it, and hangs up on an error.
+	/// The link is replaced each time, as if the machine was plugged back in.
+	fn plug_in(link: &Path) -> (mpsc::Sender<anyhow::Result<Keys>>, Running) {
+		let emulator = Emulator::new().unwrap();
+		let new_link = link.with_extension("new");
+		symlink(emulator.path(), &new_link).unwrap();
+		std::fs::rename(&new_link, link).unwrap();
+		let (sender, strokes) = mpsc::channel();
+		let running = std::thread::spawn(move || emulator.run(StenoProtocol::Gemini, Timing::default(), strokes));
+		(sender, running)
+	}
+
+	let link = std::env::temp_dir().join(format!("sordahe-emulated-{}", std::process::id()));
+	let (sender, running) = plug_in(&link);
+	let machine = Machine::open(
+		Some(link.to_str().unwrap().into()),
+		Some(StenoProtocol::Gemini),
+		SerialOptions::default(),
+		KnownMachines::new(&[]).unwrap(),
+		false,
+	)
+	.unwrap();
+	let mut sources = Sources::new().unwrap();
+	sources.add("the emulated machine".into(), Box::new(machine)).unwrap();
+
+	let dict = serde_json::from_str(include_str!("../../../dict.json")).unwrap();
+	let word_list = include_str!("../../../words.txt").parse().unwrap();
+	let mut steno: Steno = Steno::new(dict, word_list);
+	let mut out = Vec::new();
+	let mut stdout = Stdout::new(&mut out);
+	let mut send = |sender: &mpsc::Sender<anyhow::Result<Keys>>, strokes: &str| {
+		for stroke in strokes.split('/') {
+			let keys: Keys = stroke.parse().unwrap();
+			sender.send(Ok(keys)).unwrap();
+			let Stroke { keys: received, bytes } = sources.recv().unwrap().unwrap();
+			assert_eq!(received, keys);
+			assert_eq!(bytes, None);
+			stdout.write(steno.run_keys(keys).map(|()| steno.flush()).unwrap()).unwrap();
+		}
+	};
+
+	send(&sender, "TH/S/AEU");
+	sender.send(Err(anyhow!("unplugged"))).unwrap();
+	assert!(running.join().unwrap().is_err());
+
+	let (sender, running) = plug_in(&link);
+	send(&sender, "TEFT/*");
+	sender.send(Err(anyhow!("done"))).unwrap();
+	assert!(running.join().unwrap().is_err());
+	std::fs::remove_file(&link).unwrap();
+
+	drop(stdout);
+	assert_eq!(String::from_utf8(out).unwrap(), format!("This is a test{}", "\x08 \x08".repeat(" test".len())));
+}
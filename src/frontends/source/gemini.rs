@@ -0,0 +1,228 @@
+use std::io::{ErrorKind, Read};
+use std::time::Duration;
+
+use anyhow::Context as _;
+use serialport::TTYPort as TtyPort;
+use sordahe::keys::{Key, Keys};
+
+use super::serial::SerialSettings;
+use super::StrokeSource;
+use crate::frontends::session::{Captured, Tap};
+
+/// A steno machine using Gemini PR, which sends each stroke as a frame of six bytes.
+/// Only the first byte of a frame has its high bit set, which is how frames are found again after a byte is lost or garbled.
+#[derive(Debug)]
+pub struct GeminiDevice<I> {
+	inner: I,
+	/// The bytes of the frame being read.
+	frame: Vec<u8>,
+	/// The number of bytes read since the last frame that weren't part of any.
+	stray: usize,
+	/// The number of frames that were cut short or had bytes outside any frame before them.
+	bad_frames: u64,
+}
+
+const FRAME_LEN: usize = 6;
+const FRAME_START: u8 = 0x80;
+
+impl GeminiDevice<Tap<TtyPort>> {
+	pub fn open(path: &str, settings: &SerialSettings, captured: Option<&Captured>) -> anyhow::Result<Self> {
+		let timeout = settings
+			.inactivity_timeout
+			.unwrap_or(Duration::from_secs(u32::MAX.into()));
+		let inner = settings.port(path, timeout).open_native()?;
+
+		Ok(Self::new(Tap::new(inner, captured)))
+	}
+}
+
+const GEMINI_LUT: [Option<Key>; 64] = [
+	Some(Key::Z),
+	None,
+	None,
+	Some(Key::NumberBar),
+	None,
+	Some(Key::NumberBar),
+	None,
+	None,
+	Some(Key::D),
+	Some(Key::S2),
+	Some(Key::T2),
+	Some(Key::G),
+	Some(Key::L),
+	Some(Key::B),
+	Some(Key::P2),
+	None,
+	Some(Key::R2),
+	Some(Key::F),
+	Some(Key::U),
+	Some(Key::E),
+	Some(Key::Star),
+	Some(Key::Star),
+	None,
+	None,
+	None,
+	None,
+	Some(Key::Star),
+	Some(Key::Star),
+	Some(Key::O),
+	Some(Key::A),
+	Some(Key::R),
+	None,
+	Some(Key::H),
+	Some(Key::W),
+	Some(Key::P),
+	Some(Key::K),
+	Some(Key::T),
+	Some(Key::S),
+	Some(Key::S),
+	None,
+	None,
+	None,
+	Some(Key::NumberBar),
+	None,
+	Some(Key::NumberBar),
+	None,
+	None,
+	None,
+	None,
+	None,
+	None,
+	None,
+	None,
+	None,
+	None,
+	None,
+	None,
+	None,
+	None,
+	None,
+	None,
+	None,
+	None,
+	None,
+];
+
+impl<I> GeminiDevice<I> {
+	fn new(inner: I) -> Self {
+		Self {
+			inner,
+			frame: Vec::with_capacity(FRAME_LEN),
+			stray: 0,
+			bad_frames: 0,
+		}
+	}
+
+	fn drop_bad_frame(&mut self, reason: &str) {
+		self.bad_frames += 1;
+		eprintln!(
+			"dropped a bad Gemini frame: {reason} ({} bad frames so far)",
+			self.bad_frames,
+		);
+	}
+
+	/// Adds a byte to the frame being read, returning the frame if it's complete.
+	fn add_byte(&mut self, byte: u8) -> Option<[u8; FRAME_LEN]> {
+		if byte & FRAME_START > 0 {
+			if !self.frame.is_empty() {
+				let reason = format!("cut short after {:02x?}", self.frame);
+				self.drop_bad_frame(&reason);
+			}
+			if self.stray > 0 {
+				let reason = format!("{} stray bytes before it", self.stray);
+				self.drop_bad_frame(&reason);
+			}
+			self.frame.clear();
+			self.stray = 0;
+		} else if self.frame.is_empty() {
+			self.stray += 1;
+			return None;
+		}
+
+		self.frame.push(byte);
+		let frame = self.frame.as_slice().try_into().ok()?;
+		self.frame.clear();
+		Some(frame)
+	}
+}
+
+fn decode(frame: [u8; FRAME_LEN]) -> Keys {
+	let mut buf = [0u8; 8];
+	buf[2..].copy_from_slice(&frame);
+	buf[2] &= !FRAME_START;
+
+	let raw = u64::from_be_bytes(buf);
+	(0..u64::BITS)
+		.filter(|bit| raw & (1 << bit) > 0)
+		.filter_map(|bit| GEMINI_LUT[bit as usize])
+		.collect()
+}
+
+/// Encodes a stroke as a frame, as a machine would send it.
+pub(super) fn encode(keys: Keys) -> [u8; FRAME_LEN] {
+	let raw = keys
+		.into_iter()
+		.filter_map(|key| GEMINI_LUT.iter().position(|&lut_key| lut_key == Some(key)))
+		.fold(0u64, |raw, bit| raw | (1 << bit));
+	let mut frame = [0; FRAME_LEN];
+	frame.copy_from_slice(&raw.to_be_bytes()[2..]);
+	frame[0] |= FRAME_START;
+	frame
+}
+
+impl<I: Read> Iterator for GeminiDevice<I> {
+	type Item = anyhow::Result<Keys>;
+
+	fn next(&mut self) -> Option<anyhow::Result<Keys>> {
+		loop {
+			let mut byte = [0];
+			match self.inner.read(&mut byte) {
+				Ok(0) => return None,
+				Ok(_) => {}
+				Err(error) if error.kind() == ErrorKind::Interrupted => continue,
+				Err(error) if error.kind() == ErrorKind::TimedOut => {
+					return Some(Err(error).context("the machine sent nothing for the inactivity timeout"));
+				}
+				Err(error) => return Some(Err(error).context("IO error reading from device")),
+			}
+			if let Some(frame) = self.add_byte(byte[0]) {
+				return Some(Ok(decode(frame)));
+			}
+		}
+	}
+}
+
+impl<I: Read + Send> StrokeSource for GeminiDevice<I> {
+	fn read_stroke(&mut self) -> anyhow::Result<Option<Keys>> {
+		self.next().transpose()
+	}
+}
+
+#[test]
+fn test_gemini_resync() {
+	let frame = |bits: &[u32]| {
+		let raw = bits.iter().fold(0u64, |raw, bit| raw | (1 << bit));
+		let mut frame = raw.to_be_bytes();
+		frame[2] |= FRAME_START;
+		frame[2..].to_vec()
+	};
+	let strokes = |bytes: &[u8]| {
+		let mut device = GeminiDevice::new(bytes);
+		let strokes: Vec<_> = device.by_ref().map(|keys| keys.unwrap().to_string()).collect();
+		(strokes, device.bad_frames)
+	};
+
+	let teft = frame(&[36, 19, 17, 10]);
+	let kat = frame(&[35, 29, 10]);
+	assert_eq!(strokes(&[&teft[..], &kat].concat()), (vec!["TEFT".into(), "KAT".into()], 0));
+
+	// Noise right after plugging in, and a frame with its first byte lost.
+	let noisy = [&[0x12, 0x7f][..], &teft, &kat[1..], &kat].concat();
+	assert_eq!(strokes(&noisy), (vec!["TEFT".into(), "KAT".into()], 2));
+	// A frame cut short by the start of the next one.
+	let cut = [&teft[..3], &kat, &teft].concat();
+	assert_eq!(strokes(&cut), (vec!["KAT".into(), "TEFT".into()], 1));
+	// A garbled byte with its high bit set in the middle of a frame.
+	let garbled = [&teft[..2], &[0x81], &teft[3..], &kat].concat();
+	assert_eq!(strokes(&garbled), (vec!["KAT".into()], 2));
+}
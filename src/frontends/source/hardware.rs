@@ -0,0 +1,190 @@
+use std::fmt::{self, Display};
+use std::num::ParseIntError;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::LazyLock;
+
+use anyhow::{bail, ensure, Context as _};
+use serde::Deserialize;
+use serde_with::DeserializeFromStr;
+use serialport::{SerialPortType, UsbPortInfo};
+
+use super::serial::SerialOptions;
+use crate::args::StenoProtocol;
+
+/// A USB vendor or product ID, written in hex like `"1209"`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, DeserializeFromStr)]
+pub struct UsbId(u16);
+
+impl FromStr for UsbId {
+	type Err = ParseIntError;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		u16::from_str_radix(s.trim_start_matches("0x"), 16).map(Self)
+	}
+}
+
+/// A kind of steno machine, recognized by the details of its USB serial port.
+/// Every detail given must match exactly, since names like "Keyboard" would otherwise match all sorts of devices.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct KnownMachine {
+	pub name: String,
+	#[serde(default)]
+	pub vid: Option<UsbId>,
+	#[serde(default)]
+	pub pid: Option<UsbId>,
+	#[serde(default)]
+	pub manufacturer: Option<String>,
+	#[serde(default)]
+	pub product: Option<String>,
+	#[serde(default)]
+	pub protocol: StenoProtocol,
+	#[serde(default)]
+	pub serial: SerialOptions,
+}
+
+impl KnownMachine {
+	fn has_details(&self) -> bool {
+		self.vid.is_some() || self.pid.is_some() || self.manufacturer.is_some() || self.product.is_some()
+	}
+
+	fn matches(&self, info: &UsbPortInfo) -> bool {
+		let equals = |expected: &Option<String>, detail: &Option<String>| {
+			expected.is_none() || expected == detail
+		};
+		self.vid.is_none_or(|UsbId(vid)| vid == info.vid)
+			&& self.pid.is_none_or(|UsbId(pid)| pid == info.pid)
+			&& equals(&self.manufacturer, &info.manufacturer)
+			&& equals(&self.product, &info.product)
+	}
+}
+
+static BUILTIN_MACHINES: LazyLock<Vec<KnownMachine>> = LazyLock::new(|| {
+	serde_json::from_str(include_str!("../../../machines.json")).expect("built-in machines are invalid")
+});
+
+/// A serial port and its USB details, for listing ports.
+struct Port<'a>(&'a str, &'a UsbPortInfo);
+
+impl Display for Port<'_> {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		let Port(name, info) = self;
+		write!(f, "{name} ({:04x}:{:04x}", info.vid, info.pid)?;
+		for detail in [&info.manufacturer, &info.product, &info.serial_number].into_iter().flatten() {
+			write!(f, ", {detail}")?;
+		}
+		write!(f, ")")
+	}
+}
+
+fn usb_ports() -> anyhow::Result<Vec<(String, UsbPortInfo)>> {
+	let ports = serialport::available_ports().context("enumerating serial ports")?;
+	Ok(ports
+		.into_iter()
+		.filter_map(|port| {
+			let SerialPortType::UsbPort(info) = port.port_type else { return None; };
+			Some((port.port_name, info))
+		})
+		.collect())
+}
+
+/// The steno machines that are recognized, with those from the config taking priority over the built-in ones.
+#[derive(Debug, Clone)]
+pub struct KnownMachines(Vec<KnownMachine>);
+
+impl KnownMachines {
+	pub fn new(configured: &[KnownMachine]) -> anyhow::Result<Self> {
+		for machine in configured {
+			ensure!(
+				machine.has_details(),
+				"known machine {:?} has no USB details to recognize it by",
+				machine.name,
+			);
+		}
+		Ok(Self(configured.iter().chain(&*BUILTIN_MACHINES).cloned().collect()))
+	}
+
+	fn find(&self, info: &UsbPortInfo) -> Option<&KnownMachine> {
+		self.0.iter().find(|machine| machine.matches(info))
+	}
+
+	/// Finds the one known machine that's plugged in, returning its path.
+	pub fn discover(&self) -> anyhow::Result<(String, &KnownMachine)> {
+		let ports = usb_ports()?;
+		let mut found: Vec<_> = ports
+			.iter()
+			.filter_map(|(name, info)| Some((name, info, self.find(info)?)))
+			.collect();
+
+		match found.len() {
+			0 => {
+				let available: Vec<_> = ports.iter().map(|(name, info)| Port(name, info).to_string()).collect();
+				bail!(
+					"could not find a known steno machine in available ports: {available:?}; give its path with -d/--device, or add it to the known machines in the config"
+				);
+			}
+			1 => {
+				let (name, _, machine) = found.remove(0);
+				Ok((name.clone(), machine))
+			}
+			_ => {
+				let found: Vec<_> = found
+					.iter()
+					.map(|(name, info, machine)| format!("{}: {}", machine.name, Port(name, info)))
+					.collect();
+				bail!("found several steno machines, so choose one with -d/--device: {found:?}");
+			}
+		}
+	}
+
+	/// Finds the known machine at `path`, which may be a link to the port such as those in `/dev/serial/by-id`.
+	pub fn identify(&self, path: &str) -> Option<&KnownMachine> {
+		let path = Path::new(path).canonicalize().ok()?;
+		let ports = usb_ports().ok()?;
+		let (_, info) = ports
+			.iter()
+			.find(|(name, _)| Path::new(name).canonicalize().is_ok_and(|name| name == path))?;
+		self.find(info)
+	}
+}
+
+#[test]
+fn test_known_machines() {
+	let configured: Vec<KnownMachine> = serde_json::from_str(
+		r#"[
+			{"name": "Custom", "vid": "feed", "pid": "0x1337", "protocol": "tx-bolt", "serial": {"baud": 115200}},
+			{"name": "Named", "manufacturer": "Someone", "product": "Writer v2"}
+		]"#,
+	)
+	.unwrap();
+	let known = KnownMachines::new(&configured).unwrap();
+
+	let port = |vid, pid, manufacturer: &str, product: &str| UsbPortInfo {
+		vid,
+		pid,
+		serial_number: None,
+		manufacturer: Some(manufacturer.into()),
+		product: Some(product.into()),
+	};
+	let custom = known.find(&port(0xfeed, 0x1337, "Someone", "Writer")).unwrap();
+	assert_eq!(custom.name, "Custom");
+	assert!(matches!(custom.protocol, StenoProtocol::TxBolt));
+	assert_eq!(custom.serial.baud, Some(115_200));
+
+	let nolltronics = known.find(&port(0x2341, 0x1, "Noll_Electronics_LLC", "EcoSteno")).unwrap();
+	assert_eq!(nolltronics.name, "Nolltronics");
+	assert!(matches!(nolltronics.protocol, StenoProtocol::Gemini));
+	assert_eq!(nolltronics.serial.baud, Some(9600));
+	let georgi = known.find(&port(0x1209, 0x2303, "g Heavy Industries", "Georgi")).unwrap();
+	assert_eq!(georgi.name, "Georgi");
+	let uni = known.find(&port(0x9000, 0x1, "StenoKeyboards", "The Uni")).unwrap();
+	assert_eq!(uni.name, "Uni");
+	let named = known.find(&port(0x9000, 0x1, "Someone", "Writer v2")).unwrap();
+	assert_eq!(named.name, "Named");
+	assert_eq!(known.find(&port(0x9000, 0x1, "Someone", "Writer")), None);
+	assert_eq!(known.find(&port(0x9000, 0x1, "someone", "Writer v2")), None);
+
+	let vague: Vec<KnownMachine> = serde_json::from_str(r#"[{"name": "Anything"}]"#).unwrap();
+	assert!(KnownMachines::new(&vague).is_err());
+}
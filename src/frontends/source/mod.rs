@@ -0,0 +1,292 @@
+use std::io::{PipeReader, PipeWriter, Read as _, Write as _};
+use std::os::fd::{AsRawFd, RawFd};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::time::Duration;
+
+use anyhow::Context as _;
+use sordahe::keys::Keys;
+
+pub use self::emulator::{Emulator, Timing};
+use self::gemini::GeminiDevice;
+pub use self::hardware::{KnownMachine, KnownMachines};
+use self::plover_hid::PloverHidDevice;
+pub use self::serial::SerialOptions;
+use self::serial::SerialSettings;
+use self::stentura::StenturaDevice;
+use self::tx_bolt::TxBoltDevice;
+use crate::args::StenoProtocol;
+use crate::frontends::session::Captured;
+
+mod emulator;
+mod gemini;
+mod hardware;
+mod plover_hid;
+mod serial;
+mod stentura;
+mod tx_bolt;
+
+/// Something that strokes are read from, such as a steno machine.
+pub trait StrokeSource: Send {
+	/// Blocks until the next stroke, returning `None` once there are no more.
+	fn read_stroke(&mut self) -> anyhow::Result<Option<Keys>>;
+
+	/// Takes the bytes read for the strokes returned so far, if they're being captured.
+	fn take_bytes(&mut self) -> Option<Bytes> {
+		None
+	}
+}
+
+/// Bytes read from a steno machine.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Bytes {
+	/// The path of the machine.
+	pub source: String,
+	pub data: Vec<u8>,
+}
+
+/// A stroke from a source, along with the bytes it was read from if they're being captured.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Stroke {
+	pub keys: Keys,
+	pub bytes: Option<Bytes>,
+}
+
+/// Opens the steno machine at `path`, capturing the bytes read from it to `captured` if there is one.
+fn open_machine(
+	path: &str,
+	protocol: StenoProtocol,
+	settings: &SerialSettings,
+	captured: Option<&Captured>,
+) -> anyhow::Result<Box<dyn StrokeSource>> {
+	let device: anyhow::Result<Box<dyn StrokeSource>> = match protocol {
+		StenoProtocol::Gemini => {
+			GeminiDevice::open(path, settings, captured).map(|device| Box::new(device) as _)
+		}
+		StenoProtocol::TxBolt => {
+			TxBoltDevice::open(path, settings, captured).map(|device| Box::new(device) as _)
+		}
+		StenoProtocol::Stentura => {
+			StenturaDevice::open(path, settings, captured).map(|device| Box::new(device) as _)
+		}
+		StenoProtocol::PloverHid => {
+			PloverHidDevice::open(path, captured).map(|device| Box::new(device) as _)
+		}
+	};
+	device.with_context(|| format!("opening device at {path:?} with protocol {protocol:?}"))
+}
+
+/// How long to wait before the first try at reopening a machine, doubling after each failure.
+const INITIAL_BACKOFF: Duration = Duration::from_millis(100);
+const MAX_BACKOFF: Duration = Duration::from_secs(5);
+
+/// Opens a machine, returning its path along with it.
+type Opener = Box<dyn FnMut() -> anyhow::Result<(String, Box<dyn StrokeSource>)> + Send>;
+
+/// A steno machine that's opened again whenever it goes away, such as when it's unplugged or its Bluetooth link drops.
+pub struct Machine {
+	open: Opener,
+	path: String,
+	device: Option<Box<dyn StrokeSource>>,
+	/// The bytes read from the machine since its last stroke, if they're being captured.
+	captured: Option<Captured>,
+}
+
+impl Machine {
+	/// Opens the machine at `fixed_path`, or the known machine that's plugged in each time if it's `None`.
+	/// The protocol and serial settings are those of the known machine, unless they're given.
+	/// The bytes read are captured along with each stroke if `capture_bytes`.
+	pub fn open(
+		fixed_path: Option<String>,
+		protocol: Option<StenoProtocol>,
+		serial: SerialOptions,
+		known: KnownMachines,
+		capture_bytes: bool,
+	) -> anyhow::Result<Self> {
+		let captured = capture_bytes.then(Captured::default);
+		Self::new(
+			captured.clone(),
+			Box::new(move || {
+				let (path, machine) = match &fixed_path {
+					Some(path) => (path.clone(), known.identify(path)),
+					None => known.discover().map(|(path, machine)| (path, Some(machine)))?,
+				};
+				if let Some(machine) = machine {
+					eprintln!("{path} is a known machine: {}", machine.name);
+				}
+				let protocol = protocol.or(machine.map(|machine| machine.protocol)).unwrap_or_default();
+				let known_serial = machine.map(|machine| machine.serial).unwrap_or_default();
+				let settings = serial.or(known_serial).settings();
+				let device = open_machine(&path, protocol, &settings, captured.as_ref())?;
+				Ok((path, device))
+			}),
+		)
+	}
+
+	fn new(captured: Option<Captured>, mut open: Opener) -> anyhow::Result<Self> {
+		let (path, device) = open()?;
+		Ok(Self {
+			open,
+			path,
+			device: Some(device),
+			captured,
+		})
+	}
+
+	/// Tries to open the machine again until it works, waiting longer after each failure.
+	fn reopen(&mut self) -> Box<dyn StrokeSource> {
+		// Whatever was read before the machine went away didn't make a stroke.
+		if let Some(captured) = &self.captured {
+			captured.take();
+		}
+		let mut backoff = INITIAL_BACKOFF;
+		loop {
+			std::thread::sleep(backoff);
+			match (self.open)() {
+				Ok((path, device)) => {
+					eprintln!("reconnected to {path}");
+					self.path = path;
+					return device;
+				}
+				Err(error) if backoff == INITIAL_BACKOFF => {
+					eprintln!("reconnecting failed, retrying until it works: {error:#}");
+				}
+				Err(_) => {}
+			}
+			backoff = (backoff * 2).min(MAX_BACKOFF);
+		}
+	}
+}
+
+impl StrokeSource for Machine {
+	fn read_stroke(&mut self) -> anyhow::Result<Option<Keys>> {
+		loop {
+			let mut device = match self.device.take() {
+				Some(device) => device,
+				None => self.reopen(),
+			};
+			match device.read_stroke() {
+				Ok(Some(keys)) => {
+					self.device = Some(device);
+					return Ok(Some(keys));
+				}
+				Ok(None) => eprintln!("{} disconnected", self.path),
+				Err(error) => eprintln!("{} disconnected: {error:#}", self.path),
+			}
+		}
+	}
+
+	fn take_bytes(&mut self) -> Option<Bytes> {
+		let captured = self.captured.as_ref()?;
+		Some(Bytes {
+			source: self.path.clone(),
+			data: captured.take(),
+		})
+	}
+}
+
+type Message = anyhow::Result<Option<Stroke>>;
+
+/// Strokes from any number of sources, each read on its own thread.
+/// Every message also writes a byte to a pipe, so that event loops can wait for strokes along with their other events.
+#[derive(Debug)]
+pub struct Sources {
+	messages: Receiver<Message>,
+	sender: Sender<Message>,
+	wake: PipeReader,
+	waker: PipeWriter,
+	/// The number of sources that haven't ended or failed.
+	running: usize,
+}
+
+impl Sources {
+	pub fn new() -> anyhow::Result<Self> {
+		let (wake, waker) = std::io::pipe().context("creating pipe")?;
+		let (sender, messages) = mpsc::channel();
+		Ok(Self {
+			messages,
+			sender,
+			wake,
+			waker,
+			running: 0,
+		})
+	}
+
+	/// Starts reading strokes from `source`, which is called `name` in messages.
+	pub fn add(&mut self, name: String, mut source: Box<dyn StrokeSource>) -> anyhow::Result<()> {
+		let sender = self.sender.clone();
+		let mut waker = self.waker.try_clone().context("cloning pipe")?;
+		self.running += 1;
+
+		std::thread::spawn(move || loop {
+			let message = source
+				.read_stroke()
+				.map(|keys| {
+					keys.map(|keys| Stroke {
+						keys,
+						bytes: source.take_bytes(),
+					})
+				})
+				.with_context(|| format!("reading strokes from {name}"));
+			let ended = !matches!(message, Ok(Some(_)));
+			if let Ok(None) = message {
+				eprintln!("{name} has no more strokes");
+			}
+			if sender.send(message).is_err() || waker.write_all(&[0]).is_err() || ended {
+				break;
+			}
+		});
+		Ok(())
+	}
+
+	/// Whether every source has ended or failed.
+	pub fn is_empty(&self) -> bool {
+		self.running == 0
+	}
+
+	/// The file descriptor that becomes readable when `recv` has something to return.
+	pub fn wake_fd(&self) -> RawFd {
+		self.wake.as_raw_fd()
+	}
+
+	/// Blocks until a source sends a stroke, ends, or fails, returning `None` if it ended.
+	pub fn recv(&mut self) -> anyhow::Result<Option<Stroke>> {
+		let mut byte = [0];
+		self.wake.read_exact(&mut byte).context("waiting for strokes")?;
+		let message = self
+			.messages
+			.recv()
+			.expect("a message is sent before waking");
+		if !matches!(message, Ok(Some(_))) {
+			self.running -= 1;
+		}
+		message
+	}
+}
+
+#[test]
+fn test_machine_reconnects() {
+	/// A machine that fails once it runs out of strokes.
+	struct Unplugged(Vec<Keys>);
+
+	impl StrokeSource for Unplugged {
+		fn read_stroke(&mut self) -> anyhow::Result<Option<Keys>> {
+			anyhow::ensure!(!self.0.is_empty(), "unplugged");
+			Ok(Some(self.0.remove(0)))
+		}
+	}
+
+	let mut opened = 0;
+	let open = move || {
+		opened += 1;
+		let device: Box<dyn StrokeSource> = match opened {
+			1 => Box::new(Unplugged(vec![Keys::S])),
+			2 => anyhow::bail!("not plugged back in yet"),
+			_ => Box::new(Unplugged(vec![Keys::T, Keys::K])),
+		};
+		Ok((format!("machine {opened}"), device))
+	};
+	let mut machine = Machine::new(None, Box::new(open)).unwrap();
+	let strokes: Vec<_> = (0..3).map(|_| machine.read_stroke().unwrap().unwrap()).collect();
+	assert_eq!(strokes, [Keys::S, Keys::T, Keys::K]);
+	assert_eq!(machine.path, "machine 3");
+}
@@ -0,0 +1,135 @@
+use std::fs::File;
+use std::io::{ErrorKind, Read};
+
+use anyhow::Context as _;
+use sordahe::keys::{Key, Keys};
+
+use super::StrokeSource;
+use crate::frontends::session::{Captured, Tap};
+
+/// A steno machine using the Plover HID protocol, read through hidraw.
+/// Every change to the keys held sends a report of all of them, and the stroke is sent once they're all released.
+#[derive(Debug)]
+pub struct PloverHidDevice<I> {
+	inner: I,
+	keys: Keys,
+}
+
+impl PloverHidDevice<Tap<File>> {
+	pub fn open(path: &str, captured: Option<&Captured>) -> anyhow::Result<Self> {
+		Ok(Self::new(Tap::new(File::open(path)?, captured)))
+	}
+}
+
+/// The report ID of the key state report.
+const REPORT_ID: u8 = 0x50;
+const REPORT_LEN: usize = 9;
+
+/// The keys of the bits of a report, from the most significant.
+/// The bits after these are extra keys that steno doesn't use.
+const PLOVER_HID_KEYS: [Key; 23] = [
+	Key::S,
+	Key::T,
+	Key::K,
+	Key::P,
+	Key::W,
+	Key::H,
+	Key::R,
+	Key::A,
+	Key::O,
+	Key::Star,
+	Key::E,
+	Key::U,
+	Key::F,
+	Key::R2,
+	Key::P2,
+	Key::B,
+	Key::L,
+	Key::G,
+	Key::T2,
+	Key::S2,
+	Key::D,
+	Key::Z,
+	Key::NumberBar,
+];
+
+impl<I> PloverHidDevice<I> {
+	fn new(inner: I) -> Self {
+		Self {
+			inner,
+			keys: Keys::empty(),
+		}
+	}
+}
+
+/// The keys held in a key state report.
+fn report_keys(report: [u8; REPORT_LEN - 1]) -> Keys {
+	let bits = u64::from_be_bytes(report);
+	(0..)
+		.zip(PLOVER_HID_KEYS)
+		.filter(|(bit, _)| bits & (1 << (63 - bit)) > 0)
+		.map(|(_, key)| key)
+		.collect()
+}
+
+/// Encodes the keys held as a key state report.
+pub(super) fn report(keys: Keys) -> [u8; REPORT_LEN] {
+	let bits = (0..)
+		.zip(PLOVER_HID_KEYS)
+		.filter(|&(_, key)| keys.contains(key))
+		.fold(0u64, |bits, (bit, _)| bits | (1 << (63 - bit)));
+	let mut report = [REPORT_ID; REPORT_LEN];
+	report[1..].copy_from_slice(&bits.to_be_bytes());
+	report
+}
+
+impl<I: Read + Send> StrokeSource for PloverHidDevice<I> {
+	fn read_stroke(&mut self) -> anyhow::Result<Option<Keys>> {
+		loop {
+			let mut report = [0; REPORT_LEN];
+			match self.inner.read_exact(&mut report) {
+				Err(error) if error.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+				Err(error) => return Err(error).context("IO error reading from device"),
+				Ok(()) => {}
+			}
+			let [id, report @ ..] = report;
+			if id != REPORT_ID {
+				continue;
+			}
+
+			let held = report_keys(report);
+			self.keys |= held;
+			if held.is_empty() && !self.keys.is_empty() {
+				return Ok(Some(std::mem::take(&mut self.keys)));
+			}
+		}
+	}
+}
+
+#[test]
+fn test_plover_hid() {
+	let report = |keys: &[u32]| {
+		let bits = keys.iter().fold(0u64, |bits, key| bits | (1 << (63 - key)));
+		let mut report = vec![REPORT_ID];
+		report.extend(bits.to_be_bytes());
+		report
+	};
+
+	// Keys are pressed one by one and released in any order, and reports with other IDs are skipped.
+	let bytes = [
+		report(&[1]),
+		report(&[1, 10]),
+		vec![0x01; REPORT_LEN],
+		report(&[1, 10, 12, 18]),
+		report(&[12]),
+		report(&[]),
+		report(&[22, 23]),
+		report(&[]),
+	]
+	.concat();
+	let mut device = PloverHidDevice::new(&bytes[..]);
+	let strokes: Vec<_> = std::iter::from_fn(|| device.read_stroke().unwrap())
+		.map(|keys| keys.to_string())
+		.collect();
+	assert_eq!(strokes, ["TEFT", "#"]);
+}
@@ -0,0 +1,126 @@
+use std::time::Duration;
+
+use serde::Deserialize;
+use serialport::SerialPortBuilder;
+
+use crate::args::{FlowControl, Parity, VirtualKeyboardArgs};
+
+/// Settings for a machine on a serial port, any of which may be left to a fallback.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct SerialOptions {
+	pub baud: Option<u32>,
+	pub parity: Option<Parity>,
+	pub flow_control: Option<FlowControl>,
+	/// Seconds the machine may send nothing before it's taken to have stalled.
+	pub inactivity_timeout: Option<u64>,
+}
+
+impl SerialOptions {
+	pub fn from_args(args: &VirtualKeyboardArgs) -> Self {
+		Self {
+			baud: args.baud,
+			parity: args.parity,
+			flow_control: args.flow_control,
+			inactivity_timeout: args.inactivity_timeout,
+		}
+	}
+
+	/// Takes the options that aren't given here from `fallback`.
+	pub fn or(self, fallback: Self) -> Self {
+		Self {
+			baud: self.baud.or(fallback.baud),
+			parity: self.parity.or(fallback.parity),
+			flow_control: self.flow_control.or(fallback.flow_control),
+			inactivity_timeout: self.inactivity_timeout.or(fallback.inactivity_timeout),
+		}
+	}
+
+	/// Fills in the options that aren't given with the defaults.
+	pub fn settings(self) -> SerialSettings {
+		let defaults = SerialSettings::default();
+		SerialSettings {
+			baud: self.baud.unwrap_or(defaults.baud),
+			parity: self.parity.unwrap_or(defaults.parity),
+			flow_control: self.flow_control.unwrap_or(defaults.flow_control),
+			inactivity_timeout: self
+				.inactivity_timeout
+				.map(Duration::from_secs)
+				.or(defaults.inactivity_timeout),
+		}
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SerialSettings {
+	pub baud: u32,
+	pub parity: Parity,
+	pub flow_control: FlowControl,
+	/// How long the machine may send nothing before reading fails, so that it's opened again.
+	pub inactivity_timeout: Option<Duration>,
+}
+
+/// The settings of most machines, whatever their protocol; those that differ are set by the known machines.
+/// Machines that send strokes on their own are quiet between them, so there's no inactivity timeout.
+impl Default for SerialSettings {
+	fn default() -> Self {
+		Self {
+			baud: 9600,
+			parity: Parity::None,
+			flow_control: FlowControl::None,
+			inactivity_timeout: None,
+		}
+	}
+}
+
+impl SerialSettings {
+	/// Starts building a port with these settings, which times out reads after `timeout`.
+	pub fn port(&self, path: &str, timeout: Duration) -> SerialPortBuilder {
+		let parity = match self.parity {
+			Parity::None => serialport::Parity::None,
+			Parity::Odd => serialport::Parity::Odd,
+			Parity::Even => serialport::Parity::Even,
+		};
+		let flow_control = match self.flow_control {
+			FlowControl::None => serialport::FlowControl::None,
+			FlowControl::Software => serialport::FlowControl::Software,
+			FlowControl::Hardware => serialport::FlowControl::Hardware,
+		};
+		serialport::new(path, self.baud)
+			.parity(parity)
+			.flow_control(flow_control)
+			.timeout(timeout)
+	}
+}
+
+#[test]
+fn test_settings() {
+	let args = SerialOptions {
+		baud: Some(115_200),
+		..SerialOptions::default()
+	};
+	let config = SerialOptions {
+		baud: Some(57_600),
+		flow_control: Some(FlowControl::Hardware),
+		..SerialOptions::default()
+	};
+	let known = SerialOptions {
+		parity: Some(Parity::Even),
+		inactivity_timeout: Some(30),
+		..SerialOptions::default()
+	};
+	let settings = args.or(config).or(known).settings();
+	assert_eq!(
+		settings,
+		SerialSettings {
+			baud: 115_200,
+			parity: Parity::Even,
+			flow_control: FlowControl::Hardware,
+			inactivity_timeout: Some(Duration::from_secs(30)),
+		},
+	);
+
+	let defaults = SerialOptions::default().settings();
+	assert_eq!(defaults.baud, 9600);
+	assert_eq!(defaults.inactivity_timeout, None);
+}
@@ -0,0 +1,428 @@
+use std::collections::VecDeque;
+use std::io::{ErrorKind, Read, Write};
+use std::sync::mpsc::Receiver;
+use std::time::{Duration, Instant};
+
+use anyhow::{bail, ensure, Context as _};
+use nix::libc::EIO;
+use serialport::TTYPort as TtyPort;
+use sordahe::keys::{Key, Keys};
+
+use super::serial::SerialSettings;
+use super::StrokeSource;
+use crate::frontends::session::{Captured, Tap};
+
+/// A Stentura writer, polled for the strokes it writes to its realtime file.
+/// Every request packet is acknowledged by a response packet with the same sequence number.
+#[derive(Debug)]
+pub struct StenturaDevice<I> {
+	inner: I,
+	seq: u8,
+	opened: bool,
+	/// Set once the strokes from before connecting have been skipped.
+	caught_up: bool,
+	/// The position to read the realtime file from.
+	block: u16,
+	byte: u16,
+	/// Bytes of a stroke that was cut off at the end of a response.
+	partial: Vec<u8>,
+	strokes: VecDeque<Keys>,
+	poll_interval: Duration,
+	/// How long to keep asking while the writer doesn't respond, instead of giving up after `RETRIES` tries.
+	inactivity_timeout: Option<Duration>,
+}
+
+/// How long to wait for a response before asking again.
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(1);
+const RETRIES: u32 = 3;
+/// How long to wait before asking again when there were no new strokes.
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+impl StenturaDevice<Tap<TtyPort>> {
+	pub fn open(path: &str, settings: &SerialSettings, captured: Option<&Captured>) -> anyhow::Result<Self> {
+		let inner = settings.port(path, RESPONSE_TIMEOUT).open_native()?;
+		Ok(Self {
+			inactivity_timeout: settings.inactivity_timeout,
+			..Self::new(Tap::new(inner, captured), POLL_INTERVAL)
+		})
+	}
+}
+
+const SOH: u8 = 1;
+const ACTION_OPEN: u16 = 0xa;
+const ACTION_READ: u16 = 0xb;
+const DRIVE: u8 = b'A';
+const REALTIME_FILE: &[u8] = b"REALTIME.000";
+const BLOCK_LEN: u16 = 512;
+const REQUEST_HEADER_LEN: usize = 18;
+const RESPONSE_HEADER_LEN: usize = 14;
+const STROKE_LEN: usize = 4;
+
+/// The keys of the bits of a stroke, from the most significant of the first byte.
+/// The top two bits of each byte are set instead, and the first key is the unused `^`.
+const STENTURA_KEYS: [Option<Key>; 24] = [
+	None,
+	Some(Key::NumberBar),
+	Some(Key::S),
+	Some(Key::T),
+	Some(Key::K),
+	Some(Key::P),
+	Some(Key::W),
+	Some(Key::H),
+	Some(Key::R),
+	Some(Key::A),
+	Some(Key::O),
+	Some(Key::Star),
+	Some(Key::E),
+	Some(Key::U),
+	Some(Key::F),
+	Some(Key::R2),
+	Some(Key::P2),
+	Some(Key::B),
+	Some(Key::L),
+	Some(Key::G),
+	Some(Key::T2),
+	Some(Key::S2),
+	Some(Key::D),
+	Some(Key::Z),
+];
+
+/// CRC-16 as used by the packets, with the reversed 0x8005 polynomial and no initial value.
+fn crc16(data: &[u8]) -> u16 {
+	data.iter().fold(0, |crc, &byte| {
+		(0..8).fold(crc ^ u16::from(byte), |crc, _| {
+			if crc & 1 > 0 {
+				(crc >> 1) ^ 0xa001
+			} else {
+				crc >> 1
+			}
+		})
+	})
+}
+
+/// Builds a request packet, which is a header followed by any data, each with its own CRC.
+fn request_packet(seq: u8, action: u16, params: [u16; 5], data: &[u8]) -> Vec<u8> {
+	let len = if data.is_empty() {
+		REQUEST_HEADER_LEN
+	} else {
+		REQUEST_HEADER_LEN + data.len() + 2
+	};
+	let mut packet = vec![SOH, seq];
+	packet.extend(u16::try_from(len).expect("request overflowed u16").to_le_bytes());
+	packet.extend(action.to_le_bytes());
+	for param in params {
+		packet.extend(param.to_le_bytes());
+	}
+	packet.extend(crc16(&packet[1..]).to_le_bytes());
+	if !data.is_empty() {
+		packet.extend(data);
+		packet.extend(crc16(data).to_le_bytes());
+	}
+	packet
+}
+
+/// Builds the response packet that acknowledges a request, with the length of any data as its first parameter.
+fn response_packet(seq: u8, action: u16, data: &[u8]) -> Vec<u8> {
+	let len = if data.is_empty() {
+		RESPONSE_HEADER_LEN
+	} else {
+		RESPONSE_HEADER_LEN + data.len() + 2
+	};
+	let mut packet = vec![SOH, seq];
+	packet.extend(u16::try_from(len).expect("response overflowed u16").to_le_bytes());
+	packet.extend(action.to_le_bytes());
+	packet.extend(0u16.to_le_bytes());
+	packet.extend(u16::try_from(data.len()).expect("response overflowed u16").to_le_bytes());
+	packet.extend(0u16.to_le_bytes());
+	packet.extend(crc16(&packet[1..]).to_le_bytes());
+	if !data.is_empty() {
+		packet.extend(data);
+		packet.extend(crc16(data).to_le_bytes());
+	}
+	packet
+}
+
+fn le_u16(bytes: &[u8]) -> u16 {
+	u16::from_le_bytes([bytes[0], bytes[1]])
+}
+
+/// Decodes the strokes in `data`, returning the bytes of any that's cut off at the end.
+fn decode_strokes(data: &[u8], strokes: &mut VecDeque<Keys>) -> anyhow::Result<Vec<u8>> {
+	let chunks = data.chunks_exact(STROKE_LEN);
+	let partial = chunks.remainder().to_vec();
+	for chunk in chunks {
+		ensure!(
+			chunk.iter().all(|byte| byte & 0xc0 == 0xc0),
+			"malformed stroke: {chunk:02x?}"
+		);
+		let bits = chunk.iter().fold(0u32, |bits, byte| (bits << 6) | u32::from(byte & 0x3f));
+		let keys = (0..)
+			.zip(STENTURA_KEYS)
+			.filter(|(bit, _)| bits & (1 << (23 - bit)) > 0)
+			.filter_map(|(_, key)| key)
+			.collect();
+		strokes.push_back(keys);
+	}
+	Ok(partial)
+}
+
+/// Encodes a stroke as it's written to the realtime file.
+fn encode_stroke(keys: Keys) -> [u8; STROKE_LEN] {
+	let bits = (0..)
+		.zip(STENTURA_KEYS)
+		.filter(|(_, key)| key.is_some_and(|key| keys.contains(key)))
+		.fold(0u32, |bits, (bit, _)| bits | (1 << (23 - bit)));
+	let mut stroke = [0; STROKE_LEN];
+	for (i, byte) in stroke.iter_mut().enumerate() {
+		*byte = 0xc0 | ((bits >> (6 * (STROKE_LEN - 1 - i))) & 0x3f) as u8;
+	}
+	stroke
+}
+
+/// Answers requests on `port` as a writer would, with the strokes from `strokes` written to the realtime file as they come.
+/// The first read is answered as if nothing had been written yet, so that none of the strokes are skipped.
+/// Returns once `port` is closed.
+pub(super) fn serve(
+	port: &mut (impl Read + Write),
+	strokes: &Receiver<anyhow::Result<Keys>>,
+) -> anyhow::Result<()> {
+	let mut realtime = Vec::new();
+	let mut caught_up = false;
+	loop {
+		let mut header = [0; REQUEST_HEADER_LEN];
+		match port.read_exact(&mut header) {
+			Err(error) if error.kind() == ErrorKind::UnexpectedEof || error.raw_os_error() == Some(EIO) => {
+				return Ok(());
+			}
+			result => result.context("reading request")?,
+		}
+		ensure!(header[0] == SOH, "request doesn't start with SOH");
+		ensure!(
+			le_u16(&header[16..]) == crc16(&header[1..16]),
+			"request header has a bad CRC"
+		);
+		let len = usize::from(le_u16(&header[2..]));
+		if len > REQUEST_HEADER_LEN {
+			let mut data = vec![0; len - REQUEST_HEADER_LEN];
+			port.read_exact(&mut data).context("reading request data")?;
+		}
+
+		let (seq, action) = (header[1], le_u16(&header[4..]));
+		let data = match action {
+			ACTION_OPEN => Vec::new(),
+			ACTION_READ if !caught_up => {
+				caught_up = true;
+				Vec::new()
+			}
+			ACTION_READ => {
+				for keys in strokes.try_iter() {
+					realtime.extend(encode_stroke(keys?));
+				}
+				let (block, byte) = (le_u16(&header[12..]), le_u16(&header[14..]));
+				let start = (usize::from(block) * usize::from(BLOCK_LEN) + usize::from(byte)).min(realtime.len());
+				let end = (start + usize::from(le_u16(&header[10..]))).min(realtime.len());
+				realtime[start..end].to_vec()
+			}
+			_ => bail!("unsupported action {action:#x}"),
+		};
+		port.write_all(&response_packet(seq, action, &data))?;
+		port.flush()?;
+	}
+}
+
+impl<I> StenturaDevice<I> {
+	fn new(inner: I, poll_interval: Duration) -> Self {
+		Self {
+			inner,
+			seq: 0,
+			opened: false,
+			caught_up: false,
+			block: 0,
+			byte: 0,
+			partial: Vec::new(),
+			strokes: VecDeque::new(),
+			poll_interval,
+			inactivity_timeout: None,
+		}
+	}
+
+	fn advance(&mut self, len: u16) {
+		self.byte += len;
+		self.block += self.byte / BLOCK_LEN;
+		self.byte %= BLOCK_LEN;
+	}
+}
+
+/// A response's first parameter and data.
+type Response = (u16, Vec<u8>);
+
+impl<I: Read + Write> StenturaDevice<I> {
+	/// Reads the response to the request with sequence number `seq`, returning `None` at the end of input.
+	fn read_response(&mut self, seq: u8, action: u16) -> anyhow::Result<Option<Response>> {
+		let mut header = [0; RESPONSE_HEADER_LEN];
+		match self.inner.read_exact(&mut header) {
+			Err(error) if error.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+			result => result?,
+		}
+		ensure!(header[0] == SOH, "response doesn't start with SOH");
+		ensure!(
+			le_u16(&header[12..]) == crc16(&header[1..12]),
+			"response header has a bad CRC"
+		);
+		ensure!(header[1] == seq, "response to request {} instead of {seq}", header[1]);
+		ensure!(le_u16(&header[4..]) == action, "response to another action");
+		let error = le_u16(&header[6..]);
+		ensure!(error == 0, "request failed with error {error:#x}");
+
+		let len = usize::from(le_u16(&header[2..]));
+		let mut data = Vec::new();
+		if len > RESPONSE_HEADER_LEN {
+			let mut rest = vec![0; len - RESPONSE_HEADER_LEN];
+			self.inner.read_exact(&mut rest)?;
+			ensure!(rest.len() >= 2, "response data has no CRC");
+			let (rest, crc) = rest.split_at(rest.len() - 2);
+			ensure!(le_u16(crc) == crc16(rest), "response data has a bad CRC");
+			data = rest.to_vec();
+		}
+		Ok(Some((le_u16(&header[8..]), data)))
+	}
+
+	/// Sends a request and waits for its acknowledgment, asking again if it doesn't come.
+	/// It gives up after `RETRIES` tries, or once there's been no response for the inactivity timeout if there is one.
+	fn request(&mut self, action: u16, params: [u16; 5], data: &[u8]) -> anyhow::Result<Option<Response>> {
+		let start = Instant::now();
+		let mut tries = 0;
+		loop {
+			tries += 1;
+			let seq = self.seq;
+			self.seq = self.seq.wrapping_add(1);
+			self.inner.write_all(&request_packet(seq, action, params, data))?;
+			self.inner.flush()?;
+			match self.read_response(seq, action) {
+				Err(error)
+					if error
+						.downcast_ref::<std::io::Error>()
+						.is_some_and(|error| error.kind() == ErrorKind::TimedOut) => {}
+				result => return result,
+			}
+			let gave_up = match self.inactivity_timeout {
+				Some(timeout) => start.elapsed() >= timeout,
+				None => tries >= RETRIES,
+			};
+			if gave_up {
+				bail!("no response after {tries} tries");
+			}
+		}
+	}
+}
+
+impl<I: Read + Write + Send> StrokeSource for StenturaDevice<I> {
+	fn read_stroke(&mut self) -> anyhow::Result<Option<Keys>> {
+		if !self.opened {
+			let params = [DRIVE.into(), 0, 0, 0, 0];
+			let opened = self
+				.request(ACTION_OPEN, params, REALTIME_FILE)
+				.context("opening the realtime file")?;
+			if opened.is_none() {
+				return Ok(None);
+			}
+			self.opened = true;
+		}
+
+		loop {
+			if let Some(keys) = self.strokes.pop_front() {
+				return Ok(Some(keys));
+			}
+
+			let params = [1, 0, BLOCK_LEN, self.block, self.byte];
+			let response = self
+				.request(ACTION_READ, params, &[])
+				.context("reading the realtime file")?;
+			let Some((len, data)) = response else { return Ok(None); };
+
+			ensure!(usize::from(len) == data.len(), "response length doesn't match its data");
+			self.advance(len);
+			if data.is_empty() {
+				self.caught_up = true;
+				std::thread::sleep(self.poll_interval);
+				continue;
+			}
+			let data = [std::mem::take(&mut self.partial), data].concat();
+			self.partial = decode_strokes(&data, &mut self.strokes)?;
+			// Strokes written before connecting aren't meant for us.
+			if !self.caught_up {
+				self.strokes.clear();
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	/// A connection that writes to a buffer and reads recorded responses.
+	struct Recorded {
+		responses: std::io::Cursor<Vec<u8>>,
+		requests: Vec<u8>,
+	}
+
+	impl Read for Recorded {
+		fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+			self.responses.read(buf)
+		}
+	}
+
+	impl Write for Recorded {
+		fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+			self.requests.write(buf)
+		}
+
+		fn flush(&mut self) -> std::io::Result<()> {
+			Ok(())
+		}
+	}
+
+	#[test]
+	fn test_crc16() {
+		assert_eq!(crc16(b"123456789"), 0xbb3d);
+	}
+
+	#[test]
+	fn test_stentura() {
+		// ^#STKP WHRAO* EUFRPB LGTSDZ
+		let kat = [0b1100_0010, 0b1100_0100, 0b1100_0000, 0b1100_1000];
+		let teft = [0b1100_0100, 0b1100_0000, 0b1110_1000, 0b1100_1000];
+		let skipped = [0b1100_1000, 0b1100_0000, 0b1100_0000, 0b1100_0000];
+
+		let responses = [
+			response_packet(0, ACTION_OPEN, &[]),
+			response_packet(1, ACTION_READ, &skipped),
+			response_packet(2, ACTION_READ, &[]),
+			// A stroke may be cut off between responses.
+			response_packet(3, ACTION_READ, &[&kat[..], &teft[..2]].concat()),
+			response_packet(4, ACTION_READ, &teft[2..]),
+		]
+		.concat();
+		let mut device = StenturaDevice::new(
+			Recorded {
+				responses: std::io::Cursor::new(responses),
+				requests: Vec::new(),
+			},
+			Duration::ZERO,
+		);
+		let strokes: Vec<_> = std::iter::from_fn(|| device.read_stroke().unwrap())
+			.map(|keys| keys.to_string())
+			.collect();
+		assert_eq!(strokes, ["KAT", "TEFT"]);
+
+		let requests = &device.inner.requests;
+		assert_eq!(
+			requests[..REQUEST_HEADER_LEN + REALTIME_FILE.len() + 2],
+			request_packet(0, ACTION_OPEN, [DRIVE.into(), 0, 0, 0, 0], REALTIME_FILE),
+		);
+		// Each read starts where the last one ended.
+		let last_read = request_packet(5, ACTION_READ, [1, 0, BLOCK_LEN, 0, 12], &[]);
+		assert!(requests.ends_with(&last_read));
+	}
+}
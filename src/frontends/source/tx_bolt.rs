@@ -0,0 +1,179 @@
+use std::io::{ErrorKind, Read};
+use std::time::{Duration, Instant};
+
+use anyhow::{bail, Context as _};
+use serialport::TTYPort as TtyPort;
+use sordahe::keys::{Key, Keys};
+
+use super::serial::SerialSettings;
+use super::StrokeSource;
+use crate::frontends::session::{Captured, Tap};
+
+/// A steno machine using TX Bolt, which sends the keys of each stroke in up to four bytes.
+/// The top two bits of each byte are the set of keys it holds, and the bottom six are the keys from that set.
+/// Sets are sent in order, so a stroke ends at the last set, a set that doesn't come after the one before, a zero byte, or a pause.
+#[derive(Debug)]
+pub struct TxBoltDevice<I> {
+	inner: I,
+	keys: Keys,
+	last_set: Option<u8>,
+	/// When the machine last sent anything, for the inactivity timeout.
+	last_read: Instant,
+	inactivity_timeout: Option<Duration>,
+}
+
+/// How long the machine is quiet before a stroke that doesn't reach the last set is taken to be finished.
+const STROKE_PAUSE: Duration = Duration::from_millis(50);
+
+impl TxBoltDevice<Tap<TtyPort>> {
+	pub fn open(path: &str, settings: &SerialSettings, captured: Option<&Captured>) -> anyhow::Result<Self> {
+		let inner = settings.port(path, STROKE_PAUSE).open_native()?;
+		Ok(Self {
+			inactivity_timeout: settings.inactivity_timeout,
+			..Self::new(Tap::new(inner, captured))
+		})
+	}
+}
+
+const TX_BOLT_KEYS: [Key; 23] = [
+	Key::S,
+	Key::T,
+	Key::K,
+	Key::P,
+	Key::W,
+	Key::H,
+	Key::R,
+	Key::A,
+	Key::O,
+	Key::Star,
+	Key::E,
+	Key::U,
+	Key::F,
+	Key::R2,
+	Key::P2,
+	Key::B,
+	Key::L,
+	Key::G,
+	Key::T2,
+	Key::S2,
+	Key::D,
+	Key::Z,
+	Key::NumberBar,
+];
+
+const LAST_SET: u8 = 3;
+
+impl<I> TxBoltDevice<I> {
+	fn new(inner: I) -> Self {
+		Self {
+			inner,
+			keys: Keys::empty(),
+			last_set: None,
+			last_read: Instant::now(),
+			inactivity_timeout: None,
+		}
+	}
+
+	fn take(&mut self) -> Option<Keys> {
+		self.last_set = None;
+		let keys = std::mem::take(&mut self.keys);
+		(!keys.is_empty()).then_some(keys)
+	}
+
+	/// Adds the keys of a byte to the stroke, returning the stroke before it if the byte starts a new one.
+	fn add_byte(&mut self, byte: u8) -> Option<Keys> {
+		if byte == 0 {
+			return self.take();
+		}
+		let set = byte >> 6;
+		let finished = if self.last_set.is_some_and(|last| set <= last) {
+			self.take()
+		} else {
+			None
+		};
+		self.last_set = Some(set);
+		for bit in 0..6 {
+			if byte & (1 << bit) > 0 {
+				if let Some(&key) = TX_BOLT_KEYS.get(usize::from(set) * 6 + bit) {
+					self.keys |= key;
+				}
+			}
+		}
+		finished
+	}
+}
+
+/// Encodes a stroke as the bytes a machine would send for it, ending with a zero byte if it doesn't reach the last set.
+pub(super) fn encode(keys: Keys) -> Vec<u8> {
+	let mut bytes = [0u8; 4];
+	for (i, key) in TX_BOLT_KEYS.into_iter().enumerate() {
+		if keys.contains(key) {
+			bytes[i / 6] |= 1 << (i % 6);
+		}
+	}
+	let mut encoded: Vec<_> = (0..)
+		.zip(bytes)
+		.filter(|&(_, byte)| byte > 0)
+		.map(|(set, byte)| (set << 6) | byte)
+		.collect();
+	if bytes[usize::from(LAST_SET)] == 0 {
+		encoded.push(0);
+	}
+	encoded
+}
+
+impl<I: Read + Send> StrokeSource for TxBoltDevice<I> {
+	fn read_stroke(&mut self) -> anyhow::Result<Option<Keys>> {
+		loop {
+			if self.last_set == Some(LAST_SET) {
+				if let Some(keys) = self.take() {
+					return Ok(Some(keys));
+				}
+			}
+
+			let mut byte = [0];
+			match self.inner.read(&mut byte) {
+				Ok(0) => return Ok(self.take()),
+				Ok(_) => self.last_read = Instant::now(),
+				Err(error) if error.kind() == ErrorKind::TimedOut => {
+					if let Some(keys) = self.take() {
+						return Ok(Some(keys));
+					}
+					if let Some(timeout) = self.inactivity_timeout {
+						if self.last_read.elapsed() >= timeout {
+							bail!("the machine sent nothing for the inactivity timeout");
+						}
+					}
+					continue;
+				}
+				Err(error) if error.kind() == ErrorKind::Interrupted => continue,
+				Err(error) => return Err(error).context("IO error reading from device"),
+			}
+
+			if let Some(keys) = self.add_byte(byte[0]) {
+				return Ok(Some(keys));
+			}
+		}
+	}
+}
+
+#[test]
+fn test_tx_bolt() {
+	let strokes = |bytes: &[u8]| {
+		let mut device = TxBoltDevice::new(bytes);
+		std::iter::from_fn(|| device.read_stroke().unwrap())
+			.map(|keys| keys.to_string())
+			.collect::<Vec<_>>()
+	};
+
+	let byte = |set: u8, keys: u8| (set << 6) | keys;
+
+	// KAT, then TEFT, each reaching the last set.
+	let kat = [byte(0, 0b100), byte(1, 0b10), byte(3, 0b1)];
+	let teft = [byte(0, 0b10), byte(1, 0b1_0000), byte(2, 0b1), byte(3, 0b1)];
+	assert_eq!(strokes(&[&kat[..], &teft].concat()), ["KAT", "TEFT"]);
+	// A set that doesn't come after the one before starts a new stroke, and a zero byte or the end of input ends one.
+	assert_eq!(strokes(&[byte(0, 0b1), byte(0, 0b10), 0, byte(1, 0b1000)]), ["S", "T", "*"]);
+	assert_eq!(strokes(&[byte(3, 0b1_0000)]), ["#"]);
+	assert_eq!(strokes(&[]), Vec::<String>::new());
+}
@@ -0,0 +1,308 @@
+use std::fs::File;
+use std::io::{ErrorKind, Read, Write};
+use std::os::fd::AsRawFd;
+use std::sync::{Arc, Condvar, Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+use anyhow::Context as _;
+use nix::poll::{poll, PollFd, PollFlags};
+use wayland_client::protocol::wl_seat::WlSeat;
+use wayland_client::{event_created_child, Connection, Dispatch, Proxy as _, QueueHandle};
+use wayland_protocols_wlr::data_control::v1::client::zwlr_data_control_device_v1::{
+	self, ZwlrDataControlDeviceV1,
+};
+use wayland_protocols_wlr::data_control::v1::client::zwlr_data_control_manager_v1::ZwlrDataControlManagerV1;
+use wayland_protocols_wlr::data_control::v1::client::zwlr_data_control_offer_v1::{
+	self, ZwlrDataControlOfferV1,
+};
+use wayland_protocols_wlr::data_control::v1::client::zwlr_data_control_source_v1::{
+	self, ZwlrDataControlSourceV1,
+};
+
+use crate::args::PasteFor;
+
+/// When to paste output through the clipboard rather than typing it key by key.
+#[derive(Debug, Clone, Default)]
+pub struct PasteRules {
+	/// Paste outputs with at least this many characters.
+	pub threshold: Option<usize>,
+	/// Paste outputs with any character matching these.
+	pub chars: Vec<PasteFor>,
+}
+
+impl PasteRules {
+	pub fn is_enabled(&self) -> bool {
+		self.threshold.is_some() || !self.chars.is_empty()
+	}
+
+	pub fn should_paste(&self, text: &str) -> bool {
+		let long = self
+			.threshold
+			.is_some_and(|threshold| text.chars().count() >= threshold);
+		long
+			|| text.chars().any(|ch| {
+				self.chars.iter().any(|rule| match rule {
+					PasteFor::NonAscii => !ch.is_ascii(),
+					PasteFor::Newline => ch == '\n',
+				})
+			})
+	}
+}
+
+const TEXT_MIME_TYPES: &[&str] = &[
+	"text/plain;charset=utf-8",
+	"text/plain",
+	"UTF8_STRING",
+	"TEXT",
+	"STRING",
+];
+
+/// How long to wait for the clipboard owner to send its contents, or for the application to take the pasted text.
+const TRANSFER_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// The contents of a clipboard, by MIME type.
+type Contents = Vec<(String, Vec<u8>)>;
+
+#[derive(Debug, Default)]
+struct OfferData {
+	mime_types: Mutex<Vec<String>>,
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+	selection: Option<ZwlrDataControlOfferV1>,
+	/// The number of times a source sent its contents to an application.
+	sent: u64,
+}
+
+/// The state shared with the thread that dispatches clipboard events.
+#[derive(Debug, Clone, Default)]
+struct Shared(Arc<(Mutex<Inner>, Condvar)>);
+
+impl Shared {
+	fn update(&self, f: impl FnOnce(&mut Inner)) {
+		let (inner, changed) = &*self.0;
+		f(&mut inner.lock().unwrap_or_else(PoisonError::into_inner));
+		changed.notify_all();
+	}
+}
+
+impl Dispatch<ZwlrDataControlDeviceV1, ()> for Shared {
+	fn event(
+		state: &mut Self,
+		_proxy: &ZwlrDataControlDeviceV1,
+		event: zwlr_data_control_device_v1::Event,
+		(): &(),
+		_conn: &Connection,
+		_handle: &QueueHandle<Self>,
+	) {
+		match event {
+			zwlr_data_control_device_v1::Event::Selection { id } => state.update(|inner| {
+				if let Some(old) = std::mem::replace(&mut inner.selection, id) {
+					old.destroy();
+				}
+			}),
+			zwlr_data_control_device_v1::Event::PrimarySelection { id: Some(offer) } => offer.destroy(),
+			_ => {}
+		}
+	}
+
+	event_created_child!(Shared, ZwlrDataControlDeviceV1, [
+		zwlr_data_control_device_v1::EVT_DATA_OFFER_OPCODE => (ZwlrDataControlOfferV1, OfferData::default()),
+	]);
+}
+
+impl Dispatch<ZwlrDataControlOfferV1, OfferData> for Shared {
+	fn event(
+		_state: &mut Self,
+		_proxy: &ZwlrDataControlOfferV1,
+		event: zwlr_data_control_offer_v1::Event,
+		data: &OfferData,
+		_conn: &Connection,
+		_handle: &QueueHandle<Self>,
+	) {
+		if let zwlr_data_control_offer_v1::Event::Offer { mime_type } = event {
+			data.mime_types.lock().unwrap_or_else(PoisonError::into_inner).push(mime_type);
+		}
+	}
+}
+
+impl Dispatch<ZwlrDataControlSourceV1, Contents> for Shared {
+	fn event(
+		state: &mut Self,
+		source: &ZwlrDataControlSourceV1,
+		event: zwlr_data_control_source_v1::Event,
+		contents: &Contents,
+		_conn: &Connection,
+		_handle: &QueueHandle<Self>,
+	) {
+		match event {
+			zwlr_data_control_source_v1::Event::Send { mime_type, fd } => {
+				if let Some((_, data)) = contents.iter().find(|(mime, _)| *mime == mime_type) {
+					if let Err(error) = File::from(fd).write_all(data) {
+						eprintln!("sending clipboard contents failed: {error}");
+					}
+				}
+				state.update(|inner| inner.sent += 1);
+			}
+			zwlr_data_control_source_v1::Event::Cancelled => source.destroy(),
+			_ => {}
+		}
+	}
+}
+
+/// Reads all of `reader`, giving up if nothing arrives before the deadline.
+fn read_until(reader: &mut (impl Read + AsRawFd), deadline: Instant) -> anyhow::Result<Vec<u8>> {
+	let fd = reader.as_raw_fd();
+	let mut data = Vec::new();
+	let mut chunk = [0; 4096];
+	loop {
+		let remaining = deadline.saturating_duration_since(Instant::now());
+		let timeout_ms = remaining.as_millis().try_into().unwrap_or(i32::MAX);
+		let ready = poll(&mut [PollFd::new(fd, PollFlags::POLLIN)], timeout_ms)?;
+		anyhow::ensure!(ready > 0, "timed out");
+		match reader.read(&mut chunk) {
+			Ok(0) => return Ok(data),
+			Ok(len) => data.extend_from_slice(&chunk[..len]),
+			Err(error) if error.kind() == ErrorKind::Interrupted => {}
+			Err(error) => return Err(error.into()),
+		}
+	}
+}
+
+/// A paste in progress.
+#[must_use]
+pub struct Pasting {
+	saved: Option<Contents>,
+	sent_before: u64,
+}
+
+/// The Wayland clipboard, through which text can be pasted while keeping what was there before.
+#[derive(Debug)]
+pub struct Clipboard {
+	conn: Connection,
+	manager: ZwlrDataControlManagerV1,
+	device: ZwlrDataControlDeviceV1,
+	handle: QueueHandle<Shared>,
+	shared: Shared,
+}
+
+impl Clipboard {
+	pub fn new(conn: &Connection, manager: &ZwlrDataControlManagerV1, seat: &WlSeat) -> Self {
+		let mut queue = conn.new_event_queue::<Shared>();
+		let handle = queue.handle();
+		let device = manager.get_data_device(seat, &handle, ());
+
+		let mut shared = Shared::default();
+		let clipboard = Self {
+			conn: conn.clone(),
+			manager: manager.clone(),
+			device,
+			handle,
+			shared: shared.clone(),
+		};
+
+		// Sources must keep serving their contents for as long as they own the clipboard.
+		std::thread::spawn(move || loop {
+			if let Err(error) = queue.blocking_dispatch(&mut shared) {
+				eprintln!("clipboard stopped: {error}");
+				break;
+			}
+		});
+
+		clipboard
+	}
+
+	fn set(&self, contents: Contents) {
+		let mime_types: Vec<_> = contents.iter().map(|(mime, _)| mime.clone()).collect();
+		let source = self.manager.create_data_source(&self.handle, contents);
+		for mime in mime_types {
+			source.offer(mime);
+		}
+		self.device.set_selection(Some(&source));
+	}
+
+	/// Reads what's on the clipboard, in every format it's offered in.
+	fn save(&self) -> anyhow::Result<Option<Contents>> {
+		let (inner, _) = &*self.shared.0;
+		let Some(offer) = inner.lock().unwrap_or_else(PoisonError::into_inner).selection.clone() else {
+			return Ok(None);
+		};
+		let mime_types = offer
+			.data::<OfferData>()
+			.map(|data| data.mime_types.lock().unwrap_or_else(PoisonError::into_inner).clone())
+			.unwrap_or_default();
+
+		let deadline = Instant::now() + TRANSFER_TIMEOUT;
+		let mut contents = Vec::new();
+		for mime in mime_types {
+			let (mut reader, writer) = std::io::pipe().context("creating pipe")?;
+			offer.receive(mime.clone(), writer.as_raw_fd());
+			self.conn.flush().context("requesting clipboard contents")?;
+			drop(writer);
+			let data = read_until(&mut reader, deadline)
+				.with_context(|| format!("reading clipboard contents as {mime}"))?;
+			contents.push((mime, data));
+		}
+		Ok(Some(contents))
+	}
+
+	/// Puts `text` on the clipboard, ready for the application to be told to paste it.
+	/// What was there before is kept for `finish_paste` to put back.
+	pub fn start_paste(&self, text: &str) -> anyhow::Result<Pasting> {
+		let saved = self.save()?;
+
+		let (inner, _) = &*self.shared.0;
+		let sent_before = inner.lock().unwrap_or_else(PoisonError::into_inner).sent;
+		let contents = TEXT_MIME_TYPES
+			.iter()
+			.map(|mime| ((*mime).to_owned(), text.as_bytes().to_owned()))
+			.collect();
+		self.set(contents);
+
+		Ok(Pasting { saved, sent_before })
+	}
+
+	/// Waits for the application to take the pasted text, then puts back what was on the clipboard before.
+	pub fn finish_paste(&self, Pasting { saved, sent_before }: Pasting) -> anyhow::Result<()> {
+		let (inner, changed) = &*self.shared.0;
+		let (guard, wait) = changed
+			.wait_timeout_while(
+				inner.lock().unwrap_or_else(PoisonError::into_inner),
+				TRANSFER_TIMEOUT,
+				|inner| inner.sent == sent_before,
+			)
+			.unwrap_or_else(PoisonError::into_inner);
+		drop(guard);
+		if wait.timed_out() {
+			eprintln!("the application didn't take the pasted text");
+		}
+
+		match saved {
+			Some(contents) => self.set(contents),
+			None => self.device.set_selection(None),
+		}
+		self.conn.flush().context("restoring clipboard")?;
+		Ok(())
+	}
+}
+
+#[test]
+fn test_should_paste() {
+	let rules = PasteRules {
+		threshold: Some(10),
+		chars: vec![PasteFor::Newline],
+	};
+	assert!(rules.is_enabled());
+	assert!(!rules.should_paste("short"));
+	assert!(rules.should_paste("a longer phrase"));
+	assert!(rules.should_paste("two\nlines"));
+	assert!(!rules.should_paste("café"));
+
+	let rules = PasteRules {
+		threshold: None,
+		chars: vec![PasteFor::NonAscii],
+	};
+	assert!(rules.should_paste("café"));
+	assert!(!PasteRules::default().is_enabled());
+}
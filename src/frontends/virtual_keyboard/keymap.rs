@@ -0,0 +1,95 @@
+use std::fmt::Write as _;
+use std::ops::RangeInclusive;
+
+const BASE: &str = include_str!("../../../keymap.xkb");
+
+/// Keycodes after the ASCII ones in `keymap.xkb`, which are assigned to other characters as they're needed.
+const DYNAMIC_KEYCODES: RangeInclusive<u32> = 127..=255;
+const CAPACITY: usize = (*DYNAMIC_KEYCODES.end() - *DYNAMIC_KEYCODES.start() + 1) as usize;
+
+/// An XKB keymap with keys for ASCII and for whichever other characters are about to be typed.
+#[derive(Debug, Default)]
+pub struct Keymap {
+	/// The characters of the dynamic keycodes, in order.
+	dynamic: Vec<char>,
+}
+
+impl Keymap {
+	fn is_ascii(ch: char) -> bool {
+		matches!(ch, '\x08'..='\n' | ' '..='~')
+	}
+
+	/// Finds the XKB keycode that types `ch`.
+	pub fn keycode(&self, ch: char) -> Option<u32> {
+		if Self::is_ascii(ch) {
+			return Some(ch.into());
+		}
+		let index = self.dynamic.iter().position(|&dynamic| dynamic == ch)?;
+		Some(DYNAMIC_KEYCODES.start() + u32::try_from(index).unwrap())
+	}
+
+	/// Assigns keycodes to the characters of `upcoming` that don't have one, returning whether the keymap changed.
+	/// If there aren't enough keycodes left, the old ones are reassigned, and if there aren't enough at all, only the first characters get one.
+	pub fn assign(&mut self, upcoming: &str) -> bool {
+		let missing = |dynamic: &[char]| {
+			let mut missing = Vec::new();
+			for ch in upcoming.chars() {
+				if !Self::is_ascii(ch) && !dynamic.contains(&ch) && !missing.contains(&ch) {
+					missing.push(ch);
+				}
+			}
+			missing
+		};
+
+		let mut added = missing(&self.dynamic);
+		if added.is_empty() {
+			return false;
+		}
+		if self.dynamic.len() + added.len() > CAPACITY {
+			self.dynamic.clear();
+			added = missing(&[]);
+		}
+		added.truncate(CAPACITY - self.dynamic.len());
+		self.dynamic.extend(added);
+		true
+	}
+
+	pub fn to_xkb(&self) -> String {
+		let mut keycodes = String::new();
+		let mut symbols = String::new();
+		for (keycode, ch) in DYNAMIC_KEYCODES.zip(&self.dynamic) {
+			writeln!(keycodes, "<{keycode}> = {keycode};").unwrap();
+			writeln!(symbols, "key <{keycode}> {{ [ U{:04X} ] }};", u32::from(*ch)).unwrap();
+		}
+
+		BASE
+			.replace("// dynamic keycodes\n", &keycodes)
+			.replace("// dynamic symbols\n", &symbols)
+	}
+}
+
+#[test]
+fn test_assign() {
+	let mut keymap = Keymap::default();
+	assert!(!keymap.assign("plain"));
+	assert_eq!(keymap.keycode('a'), Some(97));
+	assert_eq!(keymap.keycode('é'), None);
+
+	assert!(keymap.assign("café née"));
+	assert_eq!(keymap.keycode('é'), Some(127));
+	assert!(!keymap.assign("é"));
+	assert!(keymap.assign("ñ"));
+	assert_eq!(keymap.keycode('ñ'), Some(128));
+
+	let xkb = keymap.to_xkb();
+	assert!(xkb.contains("<128> = 128;"));
+	assert!(xkb.contains("key <127> { [ U00E9 ] };"));
+	assert!(!xkb.contains("dynamic"));
+
+	// Running out of keycodes starts over with only the upcoming characters.
+	let many: String = ('\u{4e00}'..).take(CAPACITY).collect();
+	assert!(keymap.assign(&many));
+	assert_eq!(keymap.keycode('é'), None);
+	assert_eq!(keymap.keycode('\u{4e00}'), Some(127));
+	assert_eq!(keymap.keycode(many.chars().last().unwrap()), Some(255));
+}
@@ -0,0 +1,486 @@
+use std::io::{ErrorKind, Write};
+use std::os::fd::AsRawFd;
+use std::time::Duration;
+
+use anyhow::{anyhow, bail, Context as _};
+use memfd::MemfdOptions;
+use sordahe::bounded_queue::BoundedQueue;
+use sordahe::chords::ChordTable;
+use sordahe::dict::PloverCommand;
+use sordahe::steno::{Output, SpecialAction, Steno};
+use wayland_client::protocol::wl_keyboard::{KeyState, KeymapFormat};
+use wayland_client::protocol::wl_registry;
+use wayland_client::protocol::wl_seat::WlSeat;
+use wayland_client::backend::WaylandError;
+use wayland_client::{delegate_noop, Connection, Dispatch, EventQueue, QueueHandle};
+use wayland_protocols_misc::zwp_virtual_keyboard_v1::client::zwp_virtual_keyboard_manager_v1::ZwpVirtualKeyboardManagerV1;
+use wayland_protocols_misc::zwp_virtual_keyboard_v1::client::zwp_virtual_keyboard_v1::ZwpVirtualKeyboardV1;
+use wayland_protocols_wlr::data_control::v1::client::zwlr_data_control_manager_v1::ZwlrDataControlManagerV1;
+
+use self::clipboard::{Clipboard, PasteRules};
+use self::keymap::Keymap;
+use super::session::Recorder;
+use super::sink::{self, OutputSink};
+use super::config::Config;
+use super::input_method::Options;
+use super::source::{KnownMachines, Machine, SerialOptions, Sources, Stroke};
+use crate::args::{KeyboardOptions, PasteShortcut, VirtualKeyboardArgs};
+
+mod clipboard;
+mod keymap;
+
+struct NeededProxies {
+	manager: Option<ZwpVirtualKeyboardManagerV1>,
+	data_control_manager: Option<ZwlrDataControlManagerV1>,
+	seat: Option<WlSeat>,
+}
+
+const ZWP_VIRTUAL_KEYBOARD_MANAGER_V1_VERSION: u32 = 1;
+const ZWLR_DATA_CONTROL_MANAGER_V1_VERSION: u32 = 1;
+const WL_SEAT_VERSION: u32 = 8;
+
+impl Dispatch<wl_registry::WlRegistry, ()> for NeededProxies {
+	fn event(
+		state: &mut Self,
+		registry: &wl_registry::WlRegistry,
+		event: wl_registry::Event,
+		(): &(),
+		_: &Connection,
+		handle: &QueueHandle<Self>,
+	) {
+		if let wl_registry::Event::Global {
+			name, interface, ..
+		} = event
+		{
+			match interface.as_str() {
+				"zwp_virtual_keyboard_manager_v1" => {
+					let manager = registry.bind(name, ZWP_VIRTUAL_KEYBOARD_MANAGER_V1_VERSION, handle, ());
+					state.manager = Some(manager);
+				}
+				"zwlr_data_control_manager_v1" => {
+					let manager = registry.bind(name, ZWLR_DATA_CONTROL_MANAGER_V1_VERSION, handle, ());
+					state.data_control_manager = Some(manager);
+				}
+				"wl_seat" => {
+					let seat = registry.bind(name, WL_SEAT_VERSION, handle, ());
+					state.seat = Some(seat);
+				}
+				_ => {}
+			}
+		}
+	}
+}
+
+delegate_noop!(NeededProxies: ignore WlSeat);
+delegate_noop!(NeededProxies: ignore ZwpVirtualKeyboardManagerV1);
+delegate_noop!(NeededProxies: ignore ZwlrDataControlManagerV1);
+
+struct App;
+
+delegate_noop!(App: ZwpVirtualKeyboardV1);
+
+const MOD_NONE: u32 = 0;
+const MOD_SHIFT: u32 = 1 << 0;
+const MOD_CONTROL: u32 = 1 << 2;
+const GROUP: u32 = 0;
+
+const KEYCODE_BASE: u32 = 8;
+const BACKSPACE: u8 = 8;
+
+/// How quickly keys are sent, for applications that drop or reorder keys that arrive too quickly.
+#[derive(Debug, Clone, Copy)]
+struct Pacing {
+	/// The pause after each key, which also flushes each key to the compositor on its own.
+	key_delay: Duration,
+	/// The pause between deleting and typing.
+	delete_delay: Duration,
+	/// The number of keys sent before flushing, without a key delay.
+	batch: usize,
+	/// Whether to log every event sent.
+	debug: bool,
+}
+
+/// Pasting through the clipboard instead of typing.
+#[derive(Debug)]
+struct Paste {
+	clipboard: Clipboard,
+	rules: PasteRules,
+	shortcut: PasteShortcut,
+}
+
+/// Times to retry flushing while the compositor isn't reading fast enough.
+const FLUSH_RETRIES: u32 = 8;
+
+#[derive(Debug)]
+struct Keyboard {
+	inner: ZwpVirtualKeyboardV1,
+	conn: Connection,
+	serial: u32,
+	keymap: Keymap,
+	pacing: Pacing,
+	/// The number of keys sent since the last flush.
+	unflushed: usize,
+	paste: Option<Paste>,
+}
+
+impl Keyboard {
+	fn new(
+		inner: ZwpVirtualKeyboardV1,
+		conn: Connection,
+		pacing: Pacing,
+		paste: Option<Paste>,
+	) -> anyhow::Result<Self> {
+		let keyboard = Self {
+			inner,
+			conn,
+			serial: 0,
+			keymap: Keymap::default(),
+			pacing,
+			unflushed: 0,
+			paste,
+		};
+		keyboard.upload_keymap()?;
+		Ok(keyboard)
+	}
+
+	fn flush(&mut self) -> anyhow::Result<()> {
+		self.unflushed = 0;
+		let mut backoff = Duration::from_millis(1);
+		for _ in 0..FLUSH_RETRIES {
+			match self.conn.flush() {
+				Err(WaylandError::Io(error)) if error.kind() == ErrorKind::WouldBlock => {
+					if self.pacing.debug {
+						eprintln!("connection is full, retrying in {backoff:?}");
+					}
+					std::thread::sleep(backoff);
+					backoff *= 2;
+				}
+				result => return result.context("flushing keys to the compositor"),
+			}
+		}
+		bail!("the compositor didn't accept keys after {FLUSH_RETRIES} retries")
+	}
+
+	fn upload_keymap(&self) -> anyhow::Result<()> {
+		if self.pacing.debug {
+			eprintln!("keymap with {:?}", self.keymap);
+		}
+		let keymap = self.keymap.to_xkb();
+		let keymap_file = MemfdOptions::new()
+			.allow_sealing(true)
+			.close_on_exec(true)
+			.create("sordahe-keymap")
+			.context("creating keymap memfd")?;
+		keymap_file
+			.as_file()
+			.write_all(keymap.as_bytes())
+			.context("writing to memfd")?;
+
+		self.inner.keymap(
+			KeymapFormat::XkbV1 as u32,
+			keymap_file.as_raw_fd(),
+			keymap.len().try_into().unwrap(),
+		);
+		Ok(())
+	}
+
+	fn next_serial(&mut self) -> u32 {
+		let ret = self.serial;
+		self.serial += 1;
+		ret
+	}
+
+	fn key_raw(&mut self, key: u32, pressed: bool) {
+		let state = if pressed {
+			KeyState::Pressed
+		} else {
+			KeyState::Released
+		};
+		if self.pacing.debug {
+			eprintln!("key {key} {state:?}");
+		}
+		let serial = self.next_serial();
+		self.inner.key(serial, key, state as u32);
+	}
+
+	fn key(&mut self, key: u32) -> anyhow::Result<()> {
+		self.key_raw(key, true);
+		self.key_raw(key, false);
+
+		self.unflushed += 1;
+		if !self.pacing.key_delay.is_zero() {
+			self.flush()?;
+			std::thread::sleep(self.pacing.key_delay);
+		} else if self.unflushed >= self.pacing.batch {
+			self.flush()?;
+		}
+		Ok(())
+	}
+
+	fn set_modifiers(&self, ctrl: bool, shift: bool) {
+		let mut modifiers = 0;
+		if ctrl {
+			modifiers |= MOD_CONTROL;
+		}
+		if shift {
+			modifiers |= MOD_SHIFT;
+		}
+		if self.pacing.debug {
+			eprintln!("modifiers {modifiers:#x}");
+		}
+		self.inner.modifiers(modifiers, MOD_NONE, MOD_NONE, GROUP);
+	}
+
+	fn reset_modifiers(&self) {
+		self.set_modifiers(false, false);
+	}
+
+	fn type_keycode(&mut self, keycode: u32) -> anyhow::Result<()> {
+		self.key(keycode - KEYCODE_BASE)
+	}
+
+	fn backspace(&mut self) -> anyhow::Result<()> {
+		self.type_keycode(BACKSPACE.into())
+	}
+
+	/// Types `s`, first giving keys to any characters that don't have one yet.
+	fn type_str(&mut self, s: &str) -> anyhow::Result<()> {
+		for (pos, ch) in s.char_indices() {
+			if self.keymap.keycode(ch).is_none() {
+				self.keymap.assign(&s[pos..]);
+				self.upload_keymap().context("updating keymap")?;
+			}
+			let keycode = self.keymap.keycode(ch).expect("upcoming character has no keycode");
+			self.type_keycode(keycode)?;
+		}
+		Ok(())
+	}
+
+	/// Types an output, deleting first, and flushes it all.
+	fn type_output(&mut self, output: Output) -> anyhow::Result<()> {
+		if self.pacing.debug {
+			eprintln!("{output:?}");
+		}
+		let Output {
+			delete_words,
+			delete,
+			append,
+		} = output;
+
+		for _ in 0..delete.chars() {
+			self.backspace()?;
+		}
+
+		if delete_words > 0 {
+			self.set_modifiers(true, false);
+			for _ in 0..delete_words {
+				self.backspace()?;
+			}
+			self.reset_modifiers();
+		}
+
+		let deleted = delete.chars() > 0 || delete_words > 0;
+		if deleted && !append.is_empty() && !self.pacing.delete_delay.is_zero() {
+			self.flush()?;
+			std::thread::sleep(self.pacing.delete_delay);
+		}
+
+		let paste = self
+			.paste
+			.as_ref()
+			.is_some_and(|paste| paste.rules.should_paste(&append));
+		if paste {
+			self.paste_str(&append)?;
+		} else {
+			self.type_str(&append)?;
+		}
+		self.flush()
+	}
+
+	fn paste_str(&mut self, s: &str) -> anyhow::Result<()> {
+		let Some(paste) = &self.paste else { return Ok(()); };
+		let shift = matches!(paste.shortcut, PasteShortcut::CtrlShiftV);
+		if self.pacing.debug {
+			eprintln!("pasting {s:?}");
+		}
+		let pasting = paste.clipboard.start_paste(s).context("putting text on the clipboard")?;
+
+		self.set_modifiers(true, shift);
+		self.type_keycode(b'v'.into())?;
+		self.reset_modifiers();
+		self.flush()?;
+
+		let Some(paste) = &self.paste else { return Ok(()); };
+		paste.clipboard.finish_paste(pasting).context("restoring the clipboard")
+	}
+}
+
+/// The virtual keyboard as an output sink, on its own connection to the compositor.
+#[derive(Debug)]
+pub struct VirtualKeyboard {
+	keyboard: Keyboard,
+	queue: EventQueue<App>,
+}
+
+impl VirtualKeyboard {
+	pub fn connect(options: &KeyboardOptions) -> anyhow::Result<Self> {
+		let conn = Connection::connect_to_env().context("connecting to Wayland server")?;
+		let display = conn.display();
+
+		let (manager, data_control_manager, seat) = {
+			let mut needed = NeededProxies {
+				manager: None,
+				data_control_manager: None,
+				seat: None,
+			};
+
+			let mut queue = conn.new_event_queue::<NeededProxies>();
+			let handle = queue.handle();
+
+			display.get_registry(&handle, ());
+
+			queue.roundtrip(&mut needed)?;
+
+			let manager = needed
+				.manager
+				.ok_or_else(|| anyhow!("no zwp_virtual_keyboard_manager_v1 found in registry"))?;
+			let seat = needed
+				.seat
+				.ok_or_else(|| anyhow!("no wl_seat found in registry"))?;
+			(manager, needed.data_control_manager, seat)
+		};
+
+		let mut queue = conn.new_event_queue::<App>();
+		let handle = queue.handle();
+
+		let keyboard = manager.create_virtual_keyboard(&seat, &handle, ());
+		let pacing = Pacing {
+			key_delay: Duration::from_millis(options.key_delay),
+			delete_delay: Duration::from_millis(options.delete_delay),
+			batch: options.batch.max(1),
+			debug: options.debug_keys,
+		};
+		let rules = PasteRules {
+			threshold: options.paste_threshold,
+			chars: options.paste_for.clone(),
+		};
+		let paste = if rules.is_enabled() {
+			let manager = data_control_manager.ok_or_else(|| {
+				anyhow!("pasting requires zwlr_data_control_manager_v1, which was not found in registry")
+			})?;
+			Some(Paste {
+				clipboard: Clipboard::new(&conn, &manager, &seat),
+				rules,
+				shortcut: options.paste_shortcut,
+			})
+		} else {
+			None
+		};
+		let keyboard = Keyboard::new(keyboard, conn.clone(), pacing, paste)
+			.context("creating virtual keyboard")?;
+
+		queue.roundtrip(&mut App)?;
+
+		Ok(Self { keyboard, queue })
+	}
+}
+
+impl OutputSink for VirtualKeyboard {
+	fn write(&mut self, output: Output) -> anyhow::Result<()> {
+		self.keyboard.type_output(output)?;
+		self.queue.roundtrip(&mut App)?;
+		Ok(())
+	}
+}
+
+pub fn run(steno: Steno, chords: &ChordTable, args: &VirtualKeyboardArgs) -> anyhow::Result<()> {
+	let config = Config::load_or_default(args.config.as_deref())?;
+	let known = KnownMachines::new(&config.machines)?;
+	let serial = SerialOptions::from_args(args).or(config.serial);
+	let recorder = args
+		.record
+		.as_deref()
+		.map(|path| Recorder::create(path, args.record_bytes))
+		.transpose()?;
+	let capture_bytes = recorder.as_ref().is_some_and(Recorder::records_bytes);
+	let mut sources = Sources::new()?;
+	if args.device.is_empty() {
+		let machine = Machine::open(None, args.protocol, serial, known.clone(), capture_bytes)?;
+		sources.add("the steno machine".into(), Box::new(machine))?;
+	}
+	for path in &args.device {
+		let machine =
+			Machine::open(Some(path.clone()), args.protocol, serial, known.clone(), capture_bytes)?;
+		sources.add(path.clone(), Box::new(machine))?;
+	}
+
+	let Some(mut sink) = sink::open(args.output, &args.keyboard())? else {
+		// This mode has none of the input method's own options to pass on, and the keyboard options were checked to be unused.
+		let options = Options {
+			keyboard: false,
+			typing: args.keyboard(),
+			..Options::default()
+		};
+		return super::input_method::run_with_sources(
+			steno,
+			&options,
+			&config,
+			chords,
+			sources,
+			None,
+			recorder,
+		);
+	};
+
+	type_strokes(steno, sources, sink.as_mut(), recorder.as_ref())
+}
+
+/// Translates the strokes from `sources` and writes the output to `sink`, until the sources are gone or a stroke quits.
+pub fn type_strokes(
+	mut steno: Steno,
+	mut sources: Sources,
+	sink: &mut dyn OutputSink,
+	recorder: Option<&Recorder>,
+) -> anyhow::Result<()> {
+	let mut buffer = BoundedQueue::new(100);
+	let mut suspended = false;
+
+	while !sources.is_empty() {
+		let Some(Stroke { keys, bytes }) = sources.recv()? else { continue; };
+		eprintln!("{keys:#}");
+
+		// While suspended, strokes are only looked up to see if they resume.
+		if suspended {
+			let resumes = steno
+				.lookup(keys)
+				.entry
+				.commands()
+				.any(|command| matches!(command, PloverCommand::Toggle | PloverCommand::Resume));
+			if resumes {
+				eprintln!("resumed");
+				suspended = false;
+			}
+			continue;
+		}
+
+		if let Some(recorder) = recorder {
+			recorder.stroke(keys, bytes.as_ref());
+		}
+		let output = steno.run_keys(keys).map(|()| steno.flush());
+
+		match output {
+			Ok(mut output) => {
+				output.use_buffer(&mut buffer);
+				sink.write(output)?;
+			}
+			Err(SpecialAction::Quit) => break,
+			Err(SpecialAction::Toggle | SpecialAction::Suspend) => {
+				eprintln!("suspended");
+				suspended = true;
+			}
+			Err(SpecialAction::Resume) => {}
+		}
+	}
+
+	Ok(())
+}
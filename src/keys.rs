@@ -2,6 +2,7 @@ use std::fmt::{self, Debug, Display, Formatter, Write as _};
 use std::ops::{BitAnd, BitAndAssign, BitOr, BitOrAssign, BitXor, BitXorAssign, Not};
 use std::str::FromStr;
 
+use serde_with::{DeserializeFromStr, SerializeDisplay};
 use thiserror::Error;
 
 macro_rules! key_enum {
@@ -58,38 +59,6 @@ key_enum! {
 }
 
 impl Key {
-	#[allow(clippy::match_same_arms /* sequential key codes */)]
-	pub fn from_code(code: u32) -> Option<Self> {
-		Some(match code {
-			2..=11 => Self::NumberBar,
-			16 => Self::S,
-			17 => Self::T,
-			18 => Self::P,
-			19 => Self::H,
-			20 => Self::Star,
-			21 => Self::F,
-			22 => Self::P2,
-			23 => Self::L,
-			24 => Self::T2,
-			25 => Self::D,
-			30 => Self::S,
-			31 => Self::K,
-			32 => Self::W,
-			33 => Self::R,
-			34 => Self::Star,
-			35 => Self::R2,
-			36 => Self::B,
-			37 => Self::G,
-			38 => Self::S2,
-			39 => Self::Z,
-			46 => Self::A,
-			47 => Self::O,
-			48 => Self::E,
-			49 => Self::U,
-			_ => return None,
-		})
-	}
-
 	pub fn to_char(self) -> char {
 		match self {
 			Self::NumberBar => '#',
@@ -150,7 +119,7 @@ impl Display for Key {
 	}
 }
 
-#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Clone, Copy, PartialEq, Eq, Hash, DeserializeFromStr, SerializeDisplay)]
 pub struct Keys(u32);
 
 impl Keys {
@@ -425,7 +394,7 @@ impl Display for Keys {
 			for key in self {
 				let needs_dash = {
 					let second = key;
-					key.other_before().map_or(false, |first| {
+					key.other_before().is_some_and(|first| {
 						!self.into_iter().any(|key| key >= first && key < second)
 					})
 				};
@@ -477,6 +446,7 @@ impl IntoIterator for Keys {
 	}
 }
 
+#[allow(clippy::into_iter_without_iter /* `Keys` is `Copy`, so iterating by value is equivalent. */)]
 impl IntoIterator for &Keys {
 	type Item = Key;
 	type IntoIter = IntoIter;
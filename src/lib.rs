@@ -0,0 +1,28 @@
+#![deny(
+	absolute_paths_not_starting_with_crate,
+	keyword_idents,
+	macro_use_extern_crate,
+	meta_variable_misuse,
+	missing_abi,
+	missing_copy_implementations,
+	non_ascii_idents,
+	nonstandard_style,
+	noop_method_call,
+	rust_2018_idioms,
+	unused_qualifications
+)]
+#![warn(clippy::pedantic)]
+#![allow(
+	clippy::must_use_candidate,
+	clippy::missing_errors_doc,
+	clippy::missing_panics_doc
+)]
+#![forbid(unsafe_code)]
+
+pub mod bounded_queue;
+pub mod chars_or_bytes;
+pub mod chords;
+pub mod dict;
+pub mod keys;
+pub mod steno;
+pub mod word_list;
@@ -8,42 +8,56 @@
 	non_ascii_idents,
 	nonstandard_style,
 	noop_method_call,
-	pointer_structural_match,
-	private_in_public,
 	rust_2018_idioms,
 	unused_qualifications
 )]
 #![warn(clippy::pedantic)]
 #![forbid(unsafe_code)]
 
+use std::path::{Path, PathBuf};
+
 use anyhow::Context as _;
+use sordahe::chords::ChordTable;
+use sordahe::dict::Dict;
+use sordahe::steno::Steno;
+use sordahe::word_list::WordList;
 
-use crate::args::Frontend;
-use crate::dict::Dict;
-use crate::steno::Steno;
-use crate::word_list::WordList;
+use crate::args::{Args, Command};
 
 mod args;
-mod bounded_queue;
-mod chars_or_bytes;
-mod dict;
 mod frontends;
-mod keys;
-mod steno;
-mod word_list;
+mod tools;
 
-fn main() -> anyhow::Result<()> {
-	let args = args::load();
+fn load_steno(dicts: &[PathBuf], chords: &ChordTable, word_list: &Path) -> anyhow::Result<Steno> {
+	let dict = Dict::load_stack(dicts, chords)?;
+	let word_list = WordList::load(word_list)
+		.with_context(|| format!("loading word list from {}", word_list.display()))?;
+	Ok(Steno::new(dict, word_list))
+}
 
-	let dict =
-		Dict::load(&args.dict).with_context(|| format!("loading dictionary from {:?}", args.dict))?;
-	let word_list = WordList::load(&args.word_list)
-		.with_context(|| format!("loading word list from {:?}", args.word_list))?;
-	let steno = Steno::new(dict, word_list);
+fn main() -> anyhow::Result<()> {
+	let Args {
+		dict,
+		word_list,
+		chords,
+		command,
+	} = args::load();
+	let chords = ChordTable::load_or_builtin(chords.as_deref())?;
 
-	match args.frontend {
-		Frontend::InputMethod(args) => crate::frontends::input_method::run(steno, args),
-		Frontend::VirtualKeyboard(args) => crate::frontends::virtual_keyboard::run(steno, args),
+	match command {
+		Command::InputMethod(args) => {
+			frontends::input_method::run(load_steno(&dict, &chords, &word_list)?, &chords, args)
+				.context("running frontend")
+		}
+		Command::VirtualKeyboard(args) => {
+			frontends::virtual_keyboard::run(load_steno(&dict, &chords, &word_list)?, &chords, &args)
+				.context("running frontend")
+		}
+		Command::Diff(args) => tools::diff::run(&chords, args),
+		Command::Merge(args) => tools::merge::run(&chords, args),
+		Command::Suggest(args) => tools::suggest::run(&dict, &chords, args),
+		Command::Explain(args) => tools::explain::run(&dict, &chords, &word_list, args),
+		Command::EmulateMachine(args) => tools::emulate_machine::run(args),
+		Command::Replay(args) => tools::replay::run(load_steno(&dict, &chords, &word_list)?, args),
 	}
-	.context("running frontend")
 }
@@ -75,7 +75,8 @@ fn make_fallback_action(keys: Keys) -> Action {
 	make_text_action(keys.to_string().into(), keys)
 }
 
-fn split_suffix(keys: Keys) -> Option<(Keys, Keys)> {
+/// Splits the suffix keys off of `keys`, returning the keys without the suffix and the suffix keys.
+pub fn split_suffix(keys: Keys) -> Option<(Keys, Keys)> {
 	let suffix_keys = Key::G | Key::S2 | Key::D | Key::Z;
 
 	let suffix = keys & suffix_keys;
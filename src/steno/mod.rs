@@ -1,6 +1,7 @@
 pub use self::dict::Dict;
+pub use self::find_action::split_suffix;
 use self::orthography::apply_orthography_rules;
-pub use self::output::Output;
+pub use self::output::{word_deletion_len, Output};
 pub use self::word_list::WordList;
 use crate::bounded_queue::BoundedQueue;
 use crate::dict::{Entry, Strokes};
@@ -17,9 +18,13 @@ mod word_list;
 
 // Public API:
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 pub enum SpecialAction {
 	Quit,
+	/// Suspend translation if it's running, or resume it if it's suspended.
+	Toggle,
+	Suspend,
+	Resume,
 }
 
 impl<D: Dict, W: WordList> Steno<D, W> {
@@ -40,6 +45,72 @@ impl<D: Dict, W: WordList> Steno<D, W> {
 		self.run_action(action)
 	}
 
+	/// Finds the action that `run_keys` would run for `keys`, without running it.
+	pub fn lookup(&self, keys: Keys) -> Action {
+		self.find_action(keys)
+	}
+
+	/// The length in bytes of the text written by the last `entries` translations, which later strokes may still change.
+	pub fn recent_text_len(&self, entries: usize) -> usize {
+		self.backlog.last_n(entries).map(|event| event.text.len()).sum()
+	}
+
+	/// The outlines of the last `entries` translations, from the oldest, which later strokes may still replace.
+	pub fn recent_strokes(&self, entries: usize) -> impl Iterator<Item = &Strokes> {
+		self.backlog.last_n(entries).map(|event| &event.strokes)
+	}
+
+	pub fn max_strokes(&self) -> usize {
+		self.dict.max_strokes()
+	}
+
+	/// Forgets everything that was written, as if the engine was just created.
+	/// Automatic formatting stays as it was set.
+	pub fn reset(&mut self) {
+		let InputState {
+			auto_caps,
+			auto_space,
+			..
+		} = self.state;
+		self.state = InputState {
+			caps: auto_caps,
+			auto_caps,
+			auto_space,
+			..InputState::INITIAL
+		};
+		self.backlog.clear();
+		self.backlog_entry_in_progress.clear();
+		self.output_in_progress.clear();
+	}
+
+	/// Starts afresh after text that the engine didn't write, such as when the cursor is moved.
+	/// The backlog is cleared so that undo can't delete the wrong text, and capitalization and spacing are set to follow `before`, the text before the cursor.
+	pub fn set_context(&mut self, before: &str) {
+		self.reset();
+		let trimmed = before.trim_end();
+		self.state.caps =
+			self.state.auto_caps && (trimmed.is_empty() || trimmed.ends_with(['.', '!', '?']));
+		self.state.space = self.state.auto_space
+			&& before
+				.chars()
+				.next_back()
+				.is_some_and(|ch| !ch.is_whitespace() && !"([{".contains(ch));
+	}
+
+	/// Chooses whether words are capitalized at the start of sentences and separated by spaces without being asked to, such as for typing URLs.
+	/// Explicit capitalization and spacing in entries still apply. This starts afresh like `reset`.
+	pub fn set_auto_formatting(&mut self, caps: bool, space: bool) {
+		self.state.auto_caps = caps;
+		self.state.auto_space = space;
+		self.reset();
+	}
+
+	/// Switches to a different dictionary, returning the old one. This starts afresh like `reset`.
+	pub fn replace_dict(&mut self, dict: D) -> D {
+		self.reset();
+		std::mem::replace(&mut self.dict, dict)
+	}
+
 	pub fn flush(&mut self) -> Output {
 		std::mem::take(&mut self.output_in_progress)
 	}
@@ -56,6 +127,10 @@ struct InputState {
 	space: bool,
 	carry_to_next: bool,
 	glue: bool,
+	/// Whether `caps` is set at the start of sentences.
+	auto_caps: bool,
+	/// Whether `space` is set between words.
+	auto_space: bool,
 }
 
 impl InputState {
@@ -64,6 +139,8 @@ impl InputState {
 		space: false,
 		carry_to_next: false,
 		glue: false,
+		auto_caps: true,
+		auto_space: true,
 	};
 }
 
@@ -91,11 +168,11 @@ pub struct Steno<D = crate::dict::Dict, W = crate::word_list::WordList> {
 }
 
 #[derive(Debug)]
-struct Action {
-	entry: Entry,
-	strokes: Strokes,
+pub struct Action {
+	pub entry: Entry,
+	pub strokes: Strokes,
 	/// If `Some`, this should be run after `entry`.
-	removed_suffix: Option<Entry>,
+	pub removed_suffix: Option<Entry>,
 	/// The number of backlog entries that must be deleted before applying the entry.
-	delete_before: usize,
+	pub delete_before: usize,
 }
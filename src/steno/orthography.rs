@@ -1,4 +1,5 @@
-use once_cell::sync::Lazy;
+use std::sync::LazyLock;
+
 use regex::Regex;
 
 pub fn apply_orthography_rules(first: &str, second: &str) -> Option<String> {
@@ -19,7 +20,7 @@ pub fn apply_orthography_rules(first: &str, second: &str) -> Option<String> {
 		("([aeiou])([gbtnrl])", "([ei])", "$1$2$2$3"),
 	];
 
-	static RULES: Lazy<Vec<(Regex, &str)>> = Lazy::new(|| {
+	static RULES: LazyLock<Vec<(Regex, &str)>> = LazyLock::new(|| {
 		RULES_RAW
 			.iter()
 			.copied()
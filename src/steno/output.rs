@@ -35,6 +35,16 @@ impl Output {
 	}
 }
 
+/// The number of bytes to delete from the end of `before` to delete `words` words, like Ctrl+Backspace.
+pub fn word_deletion_len(before: &str, words: usize) -> usize {
+	let mut rest = before;
+	for _ in 0..words {
+		rest = rest.trim_end();
+		rest = rest.trim_end_matches(|ch: char| !ch.is_whitespace());
+	}
+	before.len() - rest.len()
+}
+
 /// XXX Use `floor_char_boundary` when stable.
 fn floor_char_boundary_p(s: &str, index: usize) -> usize {
 	if index >= s.len() {
@@ -91,3 +101,12 @@ impl Output {
 		}
 	}
 }
+
+#[test]
+fn test_word_deletion_len() {
+	assert_eq!(word_deletion_len("This is a test", 1), "test".len());
+	assert_eq!(word_deletion_len("This is a test  ", 1), "test  ".len());
+	assert_eq!(word_deletion_len("This is a test", 2), "a test".len());
+	assert_eq!(word_deletion_len("test", 3), "test".len());
+	assert_eq!(word_deletion_len("", 1), 0);
+}
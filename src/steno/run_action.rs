@@ -1,6 +1,4 @@
-use super::{
-	apply_orthography_rules, Action, Dict, InputEvent, InputState, SpecialAction, Steno, WordList,
-};
+use super::{apply_orthography_rules, Action, Dict, InputEvent, SpecialAction, Steno, WordList};
 use crate::chars_or_bytes::CharsOrBytes;
 use crate::dict::{EntryPart, PloverCommand};
 
@@ -118,8 +116,8 @@ impl<D: Dict, W: WordList> Steno<D, W> {
 				}
 				EntryPart::SpecialPunct(punct) => {
 					self.append(punct.as_str());
-					self.state.space = true;
-					self.state.caps = punct.is_sentence_end();
+					self.state.space = self.state.auto_space;
+					self.state.caps = self.state.auto_caps && punct.is_sentence_end();
 				}
 				EntryPart::SetCaps(set) => {
 					self.state.caps = *set;
@@ -144,12 +142,10 @@ impl<D: Dict, W: WordList> Steno<D, W> {
 						self.undo_stroke()?;
 					}
 					PloverCommand::Quit => return Err(SpecialAction::Quit),
-					PloverCommand::Reset => {
-						self.state = InputState::INITIAL;
-						self.backlog.clear();
-						self.backlog_entry_in_progress.clear();
-						self.output_in_progress.clear();
-					}
+					PloverCommand::Toggle => return Err(SpecialAction::Toggle),
+					PloverCommand::Suspend => return Err(SpecialAction::Suspend),
+					PloverCommand::Resume => return Err(SpecialAction::Resume),
+					PloverCommand::Reset => self.reset(),
 				},
 			}
 		}
@@ -200,7 +196,7 @@ impl<D: Dict, W: WordList> Steno<D, W> {
 
 		if !std::mem::replace(&mut self.state.carry_to_next, false) {
 			self.state.caps = false;
-			self.state.space = true;
+			self.state.space = self.state.auto_space;
 		}
 	}
 }
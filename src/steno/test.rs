@@ -1,4 +1,4 @@
-use once_cell::sync::Lazy;
+use std::sync::LazyLock;
 
 use crate::dict::{Dict, Strokes};
 use crate::keys::Keys;
@@ -25,12 +25,13 @@ fn steno_to_string(dict: &Dict, word_list: &WordList, input: &[Keys]) -> Result<
 	Ok(output.append)
 }
 
-static DICT: Lazy<Dict> = Lazy::new(|| {
+static DICT: LazyLock<Dict> = LazyLock::new(|| {
 	serde_json::from_str(include_str!("../../dict.json"))
 		.expect("dictionary parse failed in test harness")
 });
 
-static WORD_LIST: Lazy<WordList> = Lazy::new(|| include_str!("../../words.txt").parse().unwrap());
+static WORD_LIST: LazyLock<WordList> =
+	LazyLock::new(|| include_str!("../../words.txt").parse().unwrap());
 
 const TESTS: &[(&str, &str)] = &[
 	// Basic
@@ -151,3 +152,35 @@ fn test() {
 
 	assert!(success, "some tests failed");
 }
+
+#[test]
+fn test_context() {
+	let run = |before: &str, raw_input: &str| {
+		let mut steno = Steno::new(&*DICT, &*WORD_LIST);
+		steno.set_context(before);
+		for keys in raw_input.parse::<Strokes>().unwrap().0 {
+			steno.run_keys(keys).unwrap();
+		}
+		steno.flush().append
+	};
+
+	assert_eq!(run("", "TEFT"), "Test");
+	assert_eq!(run("Some text", "TEFT"), " test");
+	assert_eq!(run("The end. ", "TEFT"), "Test");
+	assert_eq!(run("The end.", "TEFT"), " Test");
+	assert_eq!(run("(", "TEFT"), "test");
+}
+
+#[test]
+fn test_auto_formatting() {
+	let mut steno = Steno::new(&*DICT, &*WORD_LIST);
+	steno.set_auto_formatting(false, false);
+	for keys in "TEFT/-P/TEFT".parse::<Strokes>().unwrap().0 {
+		steno.run_keys(keys).unwrap();
+	}
+	assert_eq!(steno.flush().append, "test.test");
+
+	steno.set_context("Some text. ");
+	steno.run_keys("TEFT".parse().unwrap()).unwrap();
+	assert_eq!(steno.flush().append, "test");
+}
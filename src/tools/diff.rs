@@ -0,0 +1,152 @@
+use std::collections::HashMap;
+
+use anyhow::Context as _;
+use sordahe::chords::ChordTable;
+use sordahe::dict::{Entry, SourceDict, SourceEntry, Strokes};
+
+use crate::args::DiffArgs;
+
+#[derive(Debug)]
+pub struct Conflict<'a> {
+	pub strokes: &'a Strokes,
+	pub entry: &'a SourceEntry,
+	/// Outlines in the old dictionary that already wrote the same translation, and still do in the new one.
+	pub existing: Vec<&'a Strokes>,
+}
+
+#[derive(Debug, Default)]
+pub struct Diff<'a> {
+	pub added: Vec<(&'a Strokes, &'a SourceEntry)>,
+	pub removed: Vec<(&'a Strokes, &'a SourceEntry)>,
+	pub changed: Vec<(&'a Strokes, &'a SourceEntry, &'a SourceEntry)>,
+	/// Outlines that were added or changed to write a translation that another outline already wrote in the old dictionary, and still writes in the new one.
+	pub conflicts: Vec<Conflict<'a>>,
+}
+
+fn sort_by_strokes<T>(items: &mut [T], strokes: impl Fn(&T) -> &Strokes) {
+	items.sort_by_cached_key(|item| strokes(item).to_string());
+}
+
+pub fn diff<'a>(old: &'a SourceDict, new: &'a SourceDict) -> Diff<'a> {
+	let mut ret = Diff::default();
+
+	let mut old_outlines: HashMap<&Entry, Vec<&Strokes>> = HashMap::new();
+	for (strokes, entry) in old.iter() {
+		old_outlines.entry(&entry.entry).or_default().push(strokes);
+	}
+
+	for (strokes, new_entry) in new.iter() {
+		match old.get(&strokes.0) {
+			Some(old_entry) if old_entry == new_entry => continue,
+			Some(old_entry) => ret.changed.push((strokes, old_entry, new_entry)),
+			None => ret.added.push((strokes, new_entry)),
+		}
+
+		let existing: Vec<_> = old_outlines
+			.get(&new_entry.entry)
+			.into_iter()
+			.flatten()
+			.copied()
+			.filter(|&other| other != strokes && new.get(&other.0) == Some(new_entry))
+			.collect();
+		if !existing.is_empty() {
+			ret.conflicts.push(Conflict {
+				strokes,
+				entry: new_entry,
+				existing,
+			});
+		}
+	}
+
+	for (strokes, old_entry) in old.iter() {
+		if new.get(&strokes.0).is_none() {
+			ret.removed.push((strokes, old_entry));
+		}
+	}
+
+	sort_by_strokes(&mut ret.added, |(strokes, _)| strokes);
+	sort_by_strokes(&mut ret.removed, |(strokes, _)| strokes);
+	sort_by_strokes(&mut ret.changed, |(strokes, _, _)| strokes);
+	sort_by_strokes(&mut ret.conflicts, |conflict| conflict.strokes);
+	for conflict in &mut ret.conflicts {
+		sort_by_strokes(&mut conflict.existing, |strokes| strokes);
+	}
+
+	ret
+}
+
+#[test]
+fn test_diff() {
+	let old: SourceDict =
+		serde_json::from_str(r#"{"TEFT": "test", "TEFTS": "tests", "-G": "{^ing}", "KAT": "cat"}"#)
+			.unwrap();
+	let new: SourceDict = serde_json::from_str(
+		r#"{"TEFT": "test", "TEFTS": "{^s}", "-G": " {^ing}", "TEFT/-S": "tests", "KAT": "cat", "KA*T": "cat"}"#,
+	)
+	.unwrap();
+	let diff = diff(&old, &new);
+
+	let strokes = |items: &[(&Strokes, &SourceEntry)]| {
+		items
+			.iter()
+			.map(|(strokes, _)| strokes.to_string())
+			.collect::<Vec<_>>()
+	};
+	assert_eq!(strokes(&diff.added), ["KA*T", "TEFT/-S"]);
+	assert!(diff.removed.is_empty());
+	// Only the meaning is compared, so the leading space in ` {^ing}` is not a change.
+	assert_eq!(diff.changed.len(), 1);
+	assert_eq!(diff.changed[0].0.to_string(), "TEFTS");
+	let conflicts = diff
+		.conflicts
+		.iter()
+		.map(|conflict| {
+			(
+				conflict.strokes.to_string(),
+				conflict
+					.existing
+					.iter()
+					.map(ToString::to_string)
+					.collect::<Vec<_>>(),
+			)
+		})
+		.collect::<Vec<_>>();
+	// `TEFTS` no longer writes "tests", so `TEFT/-S` taking it over isn't a conflict.
+	assert_eq!(conflicts, [("KA*T".into(), vec!["KAT".to_owned()])]);
+}
+
+pub fn run(chords: &ChordTable, DiffArgs { old, new }: DiffArgs) -> anyhow::Result<()> {
+	let old = SourceDict::load(&old, chords)
+		.with_context(|| format!("loading old dictionary from {}", old.display()))?;
+	let new = SourceDict::load(&new, chords)
+		.with_context(|| format!("loading new dictionary from {}", new.display()))?;
+
+	let diff = diff(&old, &new);
+
+	for (strokes, entry) in &diff.added {
+		println!("+ {strokes} {:?}", entry.raw);
+	}
+	for (strokes, entry) in &diff.removed {
+		println!("- {strokes} {:?}", entry.raw);
+	}
+	for (strokes, old_entry, new_entry) in &diff.changed {
+		println!("~ {strokes} {:?} -> {:?}", old_entry.raw, new_entry.raw);
+	}
+	for conflict in &diff.conflicts {
+		print!("! {} {:?} already written as", conflict.strokes, conflict.entry.raw);
+		for existing in &conflict.existing {
+			print!(" {existing}");
+		}
+		println!();
+	}
+
+	eprintln!(
+		"{} added, {} removed, {} changed, {} new conflicts",
+		diff.added.len(),
+		diff.removed.len(),
+		diff.changed.len(),
+		diff.conflicts.len(),
+	);
+
+	Ok(())
+}
@@ -0,0 +1,65 @@
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read};
+use std::time::Duration;
+
+use anyhow::Context as _;
+use sordahe::keys::Keys;
+
+use crate::args::EmulateMachineArgs;
+use crate::frontends::source::{Emulator, Timing};
+
+/// Reads strokes separated by whitespace, slashes or lines, like `KAT/TEFT`.
+fn read_strokes(input: impl BufRead) -> impl Iterator<Item = anyhow::Result<Keys>> {
+	input.lines().zip(1..).flat_map(|(line, number)| {
+		let strokes: Vec<_> = match line.context("reading strokes") {
+			Ok(line) => line
+				.split(|c: char| c.is_whitespace() || c == '/')
+				.filter(|stroke| !stroke.is_empty())
+				.map(|stroke| {
+					stroke
+						.parse()
+						.with_context(|| format!("parsing stroke {stroke:?} on line {number}"))
+				})
+				.collect(),
+			Err(error) => vec![Err(error)],
+		};
+		strokes
+	})
+}
+
+pub fn run(
+	EmulateMachineArgs {
+		protocol,
+		start_delay,
+		interval,
+		byte_delay,
+		strokes,
+	}: EmulateMachineArgs,
+) -> anyhow::Result<()> {
+	let input: Box<dyn Read + Send> = match &strokes {
+		Some(path) => {
+			Box::new(File::open(path).with_context(|| format!("opening strokes at {}", path.display()))?)
+		}
+		None => Box::new(std::io::stdin()),
+	};
+	let timing = Timing {
+		start_delay: Duration::from_millis(start_delay),
+		interval: Duration::from_millis(interval),
+		byte_delay: Duration::from_millis(byte_delay),
+	};
+
+	let emulator = Emulator::new()?;
+	// The path goes to stdout on its own, so that scripts can pass it to `-d`.
+	println!("{}", emulator.path());
+	eprintln!("emulating a {protocol:?} machine, waiting for it to be opened");
+	emulator.run(protocol, timing, read_strokes(BufReader::new(input)))
+}
+
+#[test]
+fn test_read_strokes() {
+	let strokes: Vec<_> = read_strokes(&b"KAT/TEFT\n\n  S-Z   #\n"[..])
+		.map(|keys| keys.unwrap().to_string())
+		.collect();
+	assert_eq!(strokes, ["KAT", "TEFT", "SZ", "#"]);
+	assert!(read_strokes(&b"KAT/Q"[..]).nth(1).unwrap().is_err());
+}
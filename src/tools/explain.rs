@@ -0,0 +1,240 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use anyhow::{ensure, Context as _};
+use sordahe::chords::ChordTable;
+use sordahe::dict::{Entry, SourceDict, SourceEntry, Strokes};
+use sordahe::keys::Keys;
+use sordahe::steno::{split_suffix, Steno};
+use sordahe::word_list::WordList;
+
+use crate::args::ExplainArgs;
+
+/// The loaded dictionaries, in priority order, as seen by the engine.
+#[derive(Debug)]
+struct SourceStack<'a> {
+	dicts: &'a [SourceDict],
+	max_strokes: usize,
+	/// How many outlines each proper prefix of a multi-stroke outline leads to.
+	prefixes: HashMap<&'a [Keys], usize>,
+}
+
+impl<'a> SourceStack<'a> {
+	fn new(dicts: &'a [SourceDict]) -> Self {
+		let mut prefixes: HashMap<&[Keys], usize> = HashMap::new();
+		for (strokes, _) in dicts.iter().flat_map(SourceDict::iter) {
+			for len in 1..strokes.num_strokes() {
+				*prefixes.entry(&strokes.0[..len]).or_default() += 1;
+			}
+		}
+
+		Self {
+			dicts,
+			max_strokes: dicts.iter().map(SourceDict::max_strokes).max().unwrap_or(0),
+			prefixes,
+		}
+	}
+
+	/// Returns the entry for `keys` along with the index of the dictionary that provides it.
+	fn source(&self, keys: &[Keys]) -> Option<(usize, &'a SourceEntry)> {
+		self
+			.dicts
+			.iter()
+			.enumerate()
+			.find_map(|(i, dict)| Some((i, dict.get(keys)?)))
+	}
+}
+
+impl sordahe::steno::Dict for &SourceStack<'_> {
+	fn get(&self, keys: &[Keys]) -> Option<Entry> {
+		self.source(keys).map(|(_, entry)| entry.entry.clone())
+	}
+
+	fn max_strokes(&self) -> usize {
+		self.max_strokes
+	}
+}
+
+/// A translation the engine could pick for a stroke, given the translations before it.
+#[derive(Debug)]
+struct Candidate<'a> {
+	/// The number of earlier translations that the outline takes the strokes of.
+	replaces: usize,
+	/// The outline as looked up, which differs from the strokes written if a suffix was folded off.
+	strokes: Strokes,
+	dict: usize,
+	entry: &'a SourceEntry,
+	/// The suffix keys that were folded off the last stroke, and their translation.
+	suffix: Option<(Keys, &'a SourceEntry)>,
+}
+
+/// The strokes of an outline that takes the strokes of the last `replaces` of the `recent` translations and then `keys`.
+fn joined_strokes(recent: &[&Strokes], replaces: usize, keys: Keys) -> Vec<Keys> {
+	recent[recent.len() - replaces..]
+		.iter()
+		.flat_map(|strokes| strokes.0.iter().copied())
+		.chain([keys])
+		.collect()
+}
+
+/// The translations the engine could pick for `keys`, whose outlines start where one of the `recent` translations does, as in the engine.
+fn candidates<'a>(stack: &SourceStack<'a>, recent: &[&Strokes], keys: Keys) -> Vec<Candidate<'a>> {
+	let mut ret = Vec::new();
+
+	let suffix = split_suffix(keys).and_then(|(without_suffix, suffix)| {
+		let (_, entry) = stack.source(&[suffix])?;
+		Some((without_suffix, suffix, entry))
+	});
+
+	for replaces in (0..=recent.len()).rev() {
+		let mut these_strokes = joined_strokes(recent, replaces, keys);
+
+		if let Some((dict, entry)) = stack.source(&these_strokes) {
+			ret.push(Candidate {
+				replaces,
+				strokes: these_strokes.clone().into(),
+				dict,
+				entry,
+				suffix: None,
+			});
+		}
+
+		if let Some((without_suffix, suffix, suffix_entry)) = suffix {
+			*these_strokes.last_mut().unwrap() = without_suffix;
+			if let Some((dict, entry)) = stack.source(&these_strokes) {
+				ret.push(Candidate {
+					replaces,
+					strokes: these_strokes.into(),
+					dict,
+					entry,
+					suffix: Some((suffix, suffix_entry)),
+				});
+			}
+		}
+	}
+
+	ret
+}
+
+/// The strokes of the translation the engine picked, which replaces the last `replaces` of the `recent` translations, and how many longer outlines start with them.
+/// Those are the outlines the engine can still reach by extending that translation.
+fn continuation(
+	stack: &SourceStack<'_>,
+	recent: &[&Strokes],
+	replaces: usize,
+	keys: Keys,
+) -> Option<(Strokes, usize)> {
+	let so_far = joined_strokes(recent, replaces, keys);
+	let &count = stack.prefixes.get(so_far.as_slice())?;
+	Some((Strokes(so_far), count))
+}
+
+fn explain(
+	stack: &SourceStack<'_>,
+	paths: &[PathBuf],
+	word_list: &WordList,
+	chords: &ChordTable,
+	outline: &Strokes,
+) {
+	println!("{outline}");
+
+	let mut steno = Steno::new(stack, word_list);
+	let strokes = &outline.0;
+
+	for &keys in strokes {
+		print!("  {keys}:");
+		for chord in chords.split(keys) {
+			if chord.is_trivial() {
+				print!(" {}", chord.keys);
+			} else {
+				print!(" {}={}", chord.keys, chord.name);
+			}
+		}
+		println!();
+
+		let action = steno.lookup(keys);
+		let chosen_folded = action.removed_suffix.is_some();
+		let mut found_chosen = false;
+
+		let recent: Vec<_> = steno.recent_strokes(stack.max_strokes).collect();
+		for candidate in candidates(stack, &recent, keys) {
+			let chosen =
+				candidate.replaces == action.delete_before && candidate.suffix.is_some() == chosen_folded;
+			found_chosen |= chosen;
+
+			print!(
+				"    {} {} {:?} ({})",
+				if chosen { '*' } else { ' ' },
+				candidate.strokes,
+				candidate.entry.raw,
+				paths[candidate.dict].display(),
+			);
+			if let Some((suffix, entry)) = candidate.suffix {
+				print!(", with suffix {suffix} {:?} folded off", entry.raw);
+			}
+			println!();
+		}
+
+		if !found_chosen {
+			println!("    * {:?} (not in the dictionary)", action.entry.plain_text());
+		}
+
+		if let Some((so_far, count)) = continuation(stack, &recent, action.delete_before, keys) {
+			println!("      {so_far} continues in {count} longer outlines");
+		}
+
+		// Only the backlog matters here, not the output.
+		let _ = steno.run_keys(keys);
+	}
+}
+
+#[test]
+fn test_continuation() {
+	let dict: SourceDict =
+		serde_json::from_str(r#"{"TKOG": "dog", "KAT": "cat", "KAT/HROG": "catalog"}"#).unwrap();
+	let dicts = [dict];
+	let stack = SourceStack::new(&dicts);
+	let word_list: WordList = "".parse().unwrap();
+	let mut steno = Steno::new(&stack, &word_list);
+
+	let _ = steno.run_keys("TKOG".parse().unwrap());
+	let keys = "KAT".parse().unwrap();
+	let action = steno.lookup(keys);
+	let recent: Vec<_> = steno.recent_strokes(stack.max_strokes).collect();
+
+	// The translation starts at the second stroke, not at the start of `TKOG/KAT`.
+	let (so_far, count) = continuation(&stack, &recent, action.delete_before, keys).unwrap();
+	assert_eq!(so_far.to_string(), "KAT");
+	assert_eq!(count, 1);
+}
+
+pub fn run(
+	dicts: &[PathBuf],
+	chords: &ChordTable,
+	word_list: &Path,
+	ExplainArgs { pseudo, outlines }: ExplainArgs,
+) -> anyhow::Result<()> {
+	ensure!(!outlines.is_empty(), "no outlines given");
+
+	let sources = dicts
+		.iter()
+		.map(|path| {
+			SourceDict::load(path, chords)
+				.with_context(|| format!("loading dictionary from {}", path.display()))
+		})
+		.collect::<anyhow::Result<Vec<_>>>()?;
+	let stack = SourceStack::new(&sources);
+	let word_list = WordList::load(word_list)
+		.with_context(|| format!("loading word list from {}", word_list.display()))?;
+
+	for outline in &outlines {
+		let outline = if pseudo {
+			chords.parse_pseudo_strokes(outline)?
+		} else {
+			outline.parse()?
+		};
+		explain(&stack, dicts, &word_list, chords, &outline);
+	}
+
+	Ok(())
+}
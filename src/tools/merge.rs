@@ -0,0 +1,184 @@
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use anyhow::{bail, Context as _};
+use sordahe::chords::ChordTable;
+use sordahe::dict::{SourceDict, SourceEntry, Strokes};
+
+use crate::args::MergeArgs;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Resolution<'a> {
+	/// Both sides agree, or only one side changed the outline. `None` means the outline was removed.
+	Take(Option<&'a SourceEntry>),
+	/// Both sides changed the outline in different ways.
+	Conflict {
+		ours: Option<&'a SourceEntry>,
+		theirs: Option<&'a SourceEntry>,
+	},
+}
+
+pub fn merge<'a>(
+	base: &'a SourceDict,
+	ours: &'a SourceDict,
+	theirs: &'a SourceDict,
+) -> Vec<(&'a Strokes, Resolution<'a>)> {
+	let all_strokes: HashSet<&Strokes> = base
+		.iter()
+		.chain(ours.iter())
+		.chain(theirs.iter())
+		.map(|(strokes, _)| strokes)
+		.collect();
+
+	let mut ret: Vec<_> = all_strokes
+		.into_iter()
+		.map(|strokes| {
+			let base = base.get(&strokes.0);
+			let ours = ours.get(&strokes.0);
+			let theirs = theirs.get(&strokes.0);
+
+			let resolution = if ours == theirs || theirs == base {
+				Resolution::Take(ours)
+			} else if ours == base {
+				Resolution::Take(theirs)
+			} else {
+				Resolution::Conflict { ours, theirs }
+			};
+			(strokes, resolution)
+		})
+		.collect();
+	ret.sort_by_cached_key(|(strokes, _)| strokes.to_string());
+	ret
+}
+
+#[test]
+fn test_merge() {
+	fn raw(entry: Option<&SourceEntry>) -> Option<&str> {
+		entry.map(|entry| &*entry.raw)
+	}
+
+	let base: SourceDict =
+		serde_json::from_str(r#"{"A": "a", "O": "o", "E": "e", "U": "u", "KA": "ka"}"#).unwrap();
+	let ours: SourceDict =
+		serde_json::from_str(r#"{"A": "a", "O": "oh", "E": "ee", "U": "u", "TA": "ta"}"#).unwrap();
+	let theirs: SourceDict =
+		serde_json::from_str(r#"{"A": "a", "O": "o", "E": "eh", "KA": "ka", "PA": "pa"}"#).unwrap();
+
+	let merged = merge(&base, &ours, &theirs);
+	let merged: Vec<_> = merged
+		.iter()
+		.map(|(strokes, resolution)| {
+			let resolution = match resolution {
+				Resolution::Take(entry) => Ok(raw(*entry)),
+				Resolution::Conflict { ours, theirs } => Err((raw(*ours), raw(*theirs))),
+			};
+			(strokes.to_string(), resolution)
+		})
+		.collect();
+
+	assert_eq!(
+		merged,
+		[
+			("A".to_owned(), Ok(Some("a"))),
+			("E".to_owned(), Err((Some("ee"), Some("eh")))),
+			("KA".to_owned(), Ok(None)),
+			("O".to_owned(), Ok(Some("oh"))),
+			("PA".to_owned(), Ok(Some("pa"))),
+			("TA".to_owned(), Ok(Some("ta"))),
+			("U".to_owned(), Ok(None)),
+		],
+	);
+}
+
+fn entry_line(strokes: &Strokes, entry: &SourceEntry) -> String {
+	let strokes = serde_json::to_string(&strokes.to_string()).unwrap();
+	let raw = serde_json::to_string(&entry.raw).unwrap();
+	format!("{strokes}: {raw}")
+}
+
+enum Block {
+	Line(String),
+	Conflict(Option<String>, Option<String>),
+}
+
+/// Writes the merged dictionary in the same line-per-entry layout as the input.
+///
+/// Conflicts are written with Git-style conflict markers so they can be resolved in a text editor.
+/// A dictionary with conflicts is not valid JSON until they are resolved.
+fn write_merged(out: &mut impl Write, merged: &[(&Strokes, Resolution<'_>)]) -> io::Result<()> {
+	let blocks: Vec<_> = merged
+		.iter()
+		.filter_map(|(strokes, resolution)| {
+			let line = |entry: Option<&SourceEntry>| entry.map(|entry| entry_line(strokes, entry));
+			Some(match resolution {
+				Resolution::Take(entry) => Block::Line(line(*entry)?),
+				Resolution::Conflict { ours, theirs } => Block::Conflict(line(*ours), line(*theirs)),
+			})
+		})
+		.collect();
+
+	writeln!(out, "{{")?;
+	for (i, block) in blocks.iter().enumerate() {
+		let comma = if i + 1 == blocks.len() { "" } else { "," };
+		match block {
+			Block::Line(line) => writeln!(out, "{line}{comma}")?,
+			Block::Conflict(ours, theirs) => {
+				writeln!(out, "<<<<<<< ours")?;
+				if let Some(ours) = ours {
+					writeln!(out, "{ours}{comma}")?;
+				}
+				writeln!(out, "=======")?;
+				if let Some(theirs) = theirs {
+					writeln!(out, "{theirs}{comma}")?;
+				}
+				writeln!(out, ">>>>>>> theirs")?;
+			}
+		}
+	}
+	writeln!(out, "}}")?;
+
+	Ok(())
+}
+
+pub fn run(
+	chords: &ChordTable,
+	MergeArgs {
+		base,
+		ours,
+		theirs,
+		output,
+	}: MergeArgs,
+) -> anyhow::Result<()> {
+	let load = |which: &str, path: &Path| {
+		SourceDict::load(path, chords)
+			.with_context(|| format!("loading {which} dictionary from {}", path.display()))
+	};
+	let base = load("base", &base)?;
+	let ours = load("our", &ours)?;
+	let theirs = load("their", &theirs)?;
+
+	let merged = merge(&base, &ours, &theirs);
+
+	let mut out = BufWriter::new(
+		File::create(&output)
+			.with_context(|| format!("creating output file {}", output.display()))?,
+	);
+	write_merged(&mut out, &merged)
+		.and_then(|()| out.flush())
+		.context("writing merged dictionary")?;
+
+	let conflicts = merged
+		.iter()
+		.filter(|(_, resolution)| matches!(resolution, Resolution::Conflict { .. }))
+		.count();
+	if conflicts > 0 {
+		bail!(
+			"{conflicts} conflicting outlines were written to {} with conflict markers",
+			output.display()
+		);
+	}
+
+	Ok(())
+}
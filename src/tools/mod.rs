@@ -0,0 +1,6 @@
+pub mod diff;
+pub mod emulate_machine;
+pub mod explain;
+pub mod merge;
+pub mod replay;
+pub mod suggest;
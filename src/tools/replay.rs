@@ -0,0 +1,69 @@
+use std::time::Duration;
+
+use sordahe::steno::{word_deletion_len, Output, Steno};
+
+use crate::args::ReplayArgs;
+use crate::frontends::session::{self, Record};
+
+/// How much of the end of the text is shown after each stroke.
+const SHOWN_TEXT: usize = 60;
+
+/// Applies an output to the text it's written after, deleting first.
+fn apply(text: &mut String, output: &Output) {
+	let len = text.floor_char_boundary(text.len().saturating_sub(output.delete.bytes()));
+	let len = len - word_deletion_len(&text[..len], output.delete_words);
+	text.truncate(len);
+	text.push_str(&output.append);
+}
+
+fn tail(text: &str) -> &str {
+	&text[text.ceil_char_boundary(text.len().saturating_sub(SHOWN_TEXT))..]
+}
+
+pub fn run(mut steno: Steno, ReplayArgs { bytes, session }: ReplayArgs) -> anyhow::Result<()> {
+	let events = session::load(&session)?;
+	let mut text = String::new();
+	for event in events {
+		let seconds = Duration::from_millis(event.ms).as_secs_f64();
+		match event.record {
+			Record::Stroke(keys) => {
+				let output = steno.run_keys(keys).map(|()| steno.flush());
+				let keys = keys.to_string();
+				match output {
+					Ok(output) => {
+						apply(&mut text, &output);
+						println!("{seconds:10.3}s  {keys:<24}{output:?}");
+						println!("{:37}{:?}", "", tail(&text));
+					}
+					Err(action) => println!("{seconds:10.3}s  {keys:<24}{action:?}"),
+				}
+			}
+			Record::Bytes { source, data } if bytes => println!("{seconds:10.3}s  {source}: {data:02x?}"),
+			Record::Bytes { .. } => {}
+		}
+	}
+	println!();
+	println!("{text}");
+	Ok(())
+}
+
+#[test]
+fn test_apply() {
+	let dict = serde_json::from_str(include_str!("../../dict.json")).unwrap();
+	let word_list = include_str!("../../words.txt").parse().unwrap();
+	let mut steno: Steno = Steno::new(dict, word_list);
+	let mut text = String::new();
+	for stroke in "TH/S/AEU/TEFT/*".split('/') {
+		steno.run_keys(stroke.parse().unwrap()).unwrap();
+		apply(&mut text, &steno.flush());
+	}
+	assert_eq!(text, "This is a");
+
+	let output = Output {
+		delete_words: 2,
+		append: "an example".into(),
+		..Output::default()
+	};
+	apply(&mut text, &output);
+	assert_eq!(text, "This an example");
+}
@@ -0,0 +1,392 @@
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+
+use anyhow::{ensure, Context as _};
+use sordahe::chords::ChordTable;
+use sordahe::dict::{EntryPart, SourceDict, Strokes};
+use sordahe::keys::{Key, Keys};
+
+use crate::args::SuggestArgs;
+
+/// Outlines used by the loaded dictionaries.
+#[derive(Debug, Default)]
+struct Taken<'a> {
+	outlines: HashSet<&'a [Keys]>,
+	/// Proper prefixes of multi-stroke outlines.
+	prefixes: HashSet<&'a [Keys]>,
+	/// Outlines of plain words and phrases, keyed by their lowercased text.
+	by_text: HashMap<String, Vec<&'a Strokes>>,
+}
+
+impl<'a> Taken<'a> {
+	fn new(dicts: &'a [SourceDict]) -> Self {
+		let mut ret = Self::default();
+
+		for (strokes, entry) in dicts.iter().flat_map(SourceDict::iter) {
+			let strokes_slice = strokes.0.as_slice();
+			ret.outlines.insert(strokes_slice);
+			for len in 1..strokes_slice.len() {
+				ret.prefixes.insert(&strokes_slice[..len]);
+			}
+
+			if let [EntryPart::Verbatim(text)] = &*entry.entry.0 {
+				ret
+					.by_text
+					.entry(text.to_lowercase())
+					.or_default()
+					.push(strokes);
+			}
+		}
+
+		for outlines in ret.by_text.values_mut() {
+			outlines.sort_by_cached_key(|strokes| rank(&strokes.0));
+			outlines.dedup();
+		}
+
+		ret
+	}
+
+	fn is_free(&self, strokes: &[Keys]) -> bool {
+		if self.outlines.contains(strokes) || self.prefixes.contains(strokes) {
+			return false;
+		}
+
+		// The longest-match search in `find_action` only looks at whole backlog entries, so once this outline is written, an existing outline that starts partway through it could no longer be reached.
+		(1..strokes.len()).all(|start| !self.prefixes.contains(&strokes[start..]))
+	}
+}
+
+fn is_valid(strokes: &[Keys]) -> bool {
+	!strokes.is_empty()
+		&& strokes
+			.iter()
+			.all(|&keys| !keys.is_empty() && keys.to_string().parse::<Keys>().ok() == Some(keys))
+}
+
+fn num_keys(strokes: &[Keys]) -> u32 {
+	strokes.iter().map(|keys| keys.bits().count_ones()).sum()
+}
+
+fn rank(strokes: &[Keys]) -> (usize, u32, String) {
+	(
+		strokes.len(),
+		num_keys(strokes),
+		Strokes(strokes.to_vec()).to_string(),
+	)
+}
+
+/// Chords for spelling out a word that has no outline, as (letters, initial chord, final chord).
+/// An empty chord means the letters can't be written on that side.
+const SPELLING_CONSONANTS: &[(&str, &str, &str)] = &[
+	("ch", "KH", "-FP"),
+	("sh", "SH", "-RB"),
+	("th", "TH", "*T"),
+	("b", "PW", "-B"),
+	("c", "K", "-BG"),
+	("d", "TK", "-D"),
+	("f", "TP", "-F"),
+	("g", "TKPW", "-G"),
+	("h", "H", ""),
+	("j", "SKWR", "-PBLG"),
+	("k", "K", "-BG"),
+	("l", "HR", "-L"),
+	("m", "PH", "-PL"),
+	("n", "TPH", "-PB"),
+	("p", "P", "-P"),
+	("q", "KW", ""),
+	("r", "R", "-R"),
+	("s", "S", "-S"),
+	("t", "T", "-T"),
+	("v", "SR", "-F"),
+	("w", "W", ""),
+	("x", "KP", "-BGS"),
+	("y", "KWR", ""),
+	("z", "STKPW", "-Z"),
+];
+
+const SPELLING_VOWELS: &[(&str, &str)] = &[
+	("ee", "AOE"),
+	("ea", "AOE"),
+	("oo", "AO"),
+	("ou", "OU"),
+	("ai", "AEU"),
+	("ay", "AEU"),
+	("oi", "OEU"),
+	("a", "A"),
+	("e", "E"),
+	("i", "EU"),
+	("o", "O"),
+	("u", "U"),
+	("y", "EU"),
+];
+
+fn chord(raw: &str) -> Option<Keys> {
+	(!raw.is_empty()).then(|| raw.parse().expect("internal spelling chord is invalid"))
+}
+
+/// Whether every key in `after` comes after every key in `before` in steno order.
+fn is_ordered(before: Keys, after: Keys) -> bool {
+	let last = before.into_iter().last();
+	let first = after.into_iter().next();
+	last.zip(first).is_none_or(|(last, first)| last < first)
+}
+
+enum Letters {
+	Vowel(Keys),
+	Consonant { initial: Keys, last: Option<Keys> },
+}
+
+fn split_letters(mut word: &str) -> Option<Vec<Letters>> {
+	let mut ret = Vec::new();
+
+	while !word.is_empty() {
+		// A "y" that starts the word before a vowel is a consonant, as in "yes".
+		let is_consonant_y = ret.is_empty()
+			&& word.strip_prefix('y').is_some_and(|rest| rest.starts_with(|ch| "aeiou".contains(ch)));
+		if let Some(&(letters, vowel)) = SPELLING_VOWELS
+			.iter()
+			.find(|(letters, _)| word.starts_with(letters))
+			.filter(|_| !is_consonant_y)
+		{
+			ret.push(Letters::Vowel(chord(vowel)?));
+			word = &word[letters.len()..];
+		} else {
+			let &(letters, initial, last) = SPELLING_CONSONANTS
+				.iter()
+				.find(|(letters, ..)| word.starts_with(letters))?;
+			ret.push(Letters::Consonant {
+				initial: chord(initial)?,
+				last: chord(last),
+			});
+			word = &word[letters.len()..];
+		}
+	}
+
+	Some(ret)
+}
+
+/// A rough outline for a word based on its spelling, with one stroke per vowel group.
+fn spell(word: &str) -> Option<Vec<Keys>> {
+	let word = word.to_ascii_lowercase();
+	// A silent "e" at the end of the word is not written.
+	let word = match word.strip_suffix('e') {
+		Some(without) if without.len() > 2 && !without.ends_with(|ch| "aeiou".contains(ch)) => without,
+		_ => &word,
+	};
+	let letters = split_letters(word)?;
+
+	let is_vowel = |letters: &Letters| matches!(letters, Letters::Vowel(..));
+	let first_vowel = letters.iter().position(is_vowel)?;
+	let last_vowel = letters.iter().rposition(is_vowel)?;
+
+	let mut strokes = Vec::new();
+	let mut current = Keys::empty();
+	let mut has_vowel = false;
+
+	for (i, letter) in letters.iter().enumerate() {
+		match *letter {
+			Letters::Vowel(keys) => {
+				current |= keys;
+				has_vowel = true;
+			}
+			Letters::Consonant { initial, last } => {
+				// Between two vowels, only the consonant right before the second vowel starts the next syllable.
+				let is_onset = i < first_vowel
+					|| (i < last_vowel && letters.get(i + 1).is_some_and(is_vowel));
+				let last = last.filter(|&keys| {
+					!is_onset && (current & keys).is_empty() && is_ordered(current, keys)
+				});
+
+				if let Some(keys) = last {
+					current |= keys;
+				} else {
+					if has_vowel || !(current & initial).is_empty() {
+						strokes.push(std::mem::take(&mut current));
+						has_vowel = false;
+					}
+					current |= initial;
+				}
+			}
+		}
+	}
+
+	if !current.is_empty() {
+		strokes.push(current);
+	}
+
+	Some(strokes)
+}
+
+#[test]
+fn test_spell() {
+	let spell = |word| Strokes(spell(word).unwrap()).to_string();
+	assert_eq!(spell("cat"), "KAT");
+	assert_eq!(spell("make"), "PHABG");
+	assert_eq!(spell("sorda"), "SOR/TKA");
+	assert_eq!(spell("banana"), "PWA/TPHA/TPHA");
+	assert_eq!(spell("yes"), "KWRES");
+	assert_eq!(spell("gym"), "TKPWEUPL");
+}
+
+/// All ways of combining adjacent strokes whose keys don't overlap into single strokes.
+fn foldings(strokes: &[Keys]) -> Vec<Vec<Keys>> {
+	let Some((&first, rest)) = strokes.split_first() else {
+		return vec![Vec::new()];
+	};
+
+	let mut all = Vec::new();
+	for mut tail in foldings(rest) {
+		let mut separate = Vec::with_capacity(tail.len() + 1);
+		separate.push(first);
+		separate.extend_from_slice(&tail);
+		all.push(separate);
+
+		if let Some(next) = tail.first_mut() {
+			if (*next & first).is_empty() {
+				*next |= first;
+				all.push(tail);
+			}
+		}
+	}
+	all
+}
+
+#[test]
+fn test_foldings() {
+	let foldings = |raw: &str| {
+		let mut ret = foldings(&raw.parse::<Strokes>().unwrap().0)
+			.into_iter()
+			.map(|strokes| Strokes(strokes).to_string())
+			.collect::<Vec<_>>();
+		ret.sort();
+		ret
+	};
+	assert_eq!(foldings("S/-T"), ["S-T", "S/-T"]);
+	assert_eq!(foldings("S/-T/-T"), ["S-T/-T", "S/-T/-T"]);
+}
+
+/// Limits the number of outlines combined for a phrase, which otherwise grows exponentially with its length.
+const MAX_COMBINATIONS: usize = 256;
+/// The number of existing outlines of each word that are used to build candidates.
+const OUTLINES_PER_WORD: usize = 3;
+
+fn candidates(taken: &Taken<'_>, phrase: &[&str]) -> Vec<Vec<Keys>> {
+	let per_word: Vec<Vec<Vec<Keys>>> = phrase
+		.iter()
+		.map(|word| {
+			let existing = taken.by_text.get(&word.to_lowercase());
+			let mut ret: Vec<_> = existing
+				.into_iter()
+				.flatten()
+				.take(OUTLINES_PER_WORD)
+				.map(|strokes| strokes.0.clone())
+				.collect();
+			if ret.is_empty() {
+				ret.extend(spell(word));
+			}
+			ret
+		})
+		.collect();
+
+	let mut combinations: Vec<Vec<Keys>> = vec![Vec::new()];
+	for options in &per_word {
+		combinations = combinations
+			.iter()
+			.flat_map(|prefix| {
+				options
+					.iter()
+					.map(move |option| [prefix.as_slice(), option].concat())
+			})
+			.take(MAX_COMBINATIONS)
+			.collect();
+	}
+
+	let mut ret = HashSet::new();
+	for combination in combinations {
+		for folded in foldings(&combination) {
+			for i in 0..folded.len() {
+				if !folded[i].contains(Key::Star) {
+					let mut starred = folded.clone();
+					starred[i] |= Key::Star;
+					ret.insert(starred);
+				}
+			}
+			ret.insert(folded);
+		}
+	}
+
+	let mut ret: Vec<_> = ret
+		.into_iter()
+		.filter(|strokes| is_valid(strokes) && taken.is_free(strokes))
+		.collect();
+	ret.sort_by_cached_key(|strokes| rank(strokes));
+	ret
+}
+
+#[test]
+fn test_candidates() {
+	let dict: SourceDict = serde_json::from_str(
+		r#"{"AZ": "as", "WEL": "well", "AZ/WEL/AZ": "as well as", "WEL/-D": "welled", "S*": "{^s}"}"#,
+	)
+	.unwrap();
+	let dicts = [dict];
+	let taken = Taken::new(&dicts);
+
+	assert!(taken.is_free(&"TEFT".parse::<Strokes>().unwrap().0));
+	// Taken.
+	assert!(!taken.is_free(&"AZ/WEL/AZ".parse::<Strokes>().unwrap().0));
+	// A prefix of an existing outline.
+	assert!(!taken.is_free(&"AZ/WEL".parse::<Strokes>().unwrap().0));
+	// Would stop `WEL/-D` from being written after it.
+	assert!(!taken.is_free(&"AUZ/WEL".parse::<Strokes>().unwrap().0));
+
+	let candidates = candidates(&taken, &["as", "well"])
+		.into_iter()
+		.map(|strokes| Strokes(strokes).to_string())
+		.collect::<Vec<_>>();
+	assert_eq!(candidates, ["WAELZ", "WA*ELZ", "AZ/W*EL"]);
+}
+
+pub fn run(
+	dicts: &[PathBuf],
+	chords: &ChordTable,
+	SuggestArgs { count, phrase }: SuggestArgs,
+) -> anyhow::Result<()> {
+	ensure!(!phrase.is_empty(), "no word or phrase given");
+
+	let dicts = dicts
+		.iter()
+		.map(|path| {
+			SourceDict::load(path, chords)
+				.with_context(|| format!("loading dictionary from {}", path.display()))
+		})
+		.collect::<anyhow::Result<Vec<_>>>()?;
+	let taken = Taken::new(&dicts);
+
+	let words: Vec<&str> = phrase.iter().flat_map(|part| part.split_whitespace()).collect();
+	let text = words.join(" ");
+
+	if let Some(existing) = taken.by_text.get(&text.to_lowercase()) {
+		eprint!("{text:?} is already written as");
+		for strokes in existing {
+			eprint!(" {strokes}");
+		}
+		eprintln!();
+	}
+
+	let candidates = candidates(&taken, &words);
+	if candidates.is_empty() {
+		eprintln!("no free outlines found");
+	}
+
+	for strokes in candidates.into_iter().take(count) {
+		println!(
+			"{} (strokes: {}, keys: {})",
+			Strokes(strokes.clone()),
+			strokes.len(),
+			num_keys(&strokes),
+		);
+	}
+
+	Ok(())
+}
@@ -12,7 +12,8 @@ pub struct WordList {
 
 impl WordList {
 	pub fn load(path: &Path) -> anyhow::Result<Self> {
-		let raw = std::fs::read_to_string(path).with_context(|| format!("reading from {path:?}"))?;
+		let raw = std::fs::read_to_string(path)
+			.with_context(|| format!("reading from {}", path.display()))?;
 		Ok(raw.parse().unwrap())
 	}
 